@@ -0,0 +1,295 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::date::parse_http_date;
+use crate::{HttpStatus, Response};
+
+/// On-disk cache of GET responses which follows their `Cache-Control`,
+/// `Expires` and `ETag` headers, see [`crate::Client::set_http_cache`].
+///
+/// Every url gets a `<hash>.head` file with the status, the expiry, the etag and
+/// the header lines, and a `<hash>.body` file. The url itself is not stored,
+/// because it may contain credentials like an access token.
+#[derive(Clone)]
+pub(crate) struct HttpCache {
+    dir: PathBuf,
+}
+
+/// A cached response.
+pub(crate) struct CacheEntry {
+    status: HttpStatus,
+    expires_at: SystemTime,
+    pub(crate) etag: Option<String>,
+    headers: Vec<String>,
+    body: Vec<u8>,
+}
+
+impl CacheEntry {
+    /// Whether the entry may be used without asking the server at **now**.
+    pub(crate) fn is_fresh(&self, now: SystemTime) -> bool {
+        now < self.expires_at
+    }
+
+    /// Returns the cached response of **url**.
+    pub(crate) fn to_response(&self, url: &str) -> Response {
+        Response {
+            url: url.to_string(),
+            status: self.status,
+            headers: self.headers.clone(),
+            body: Some(self.body.clone()),
+            transfer_length: 0,
+            content_length: self.body.len() as u64,
+            bytes_sent: 0,
+            bytes_received: 0,
+            sent_request_id: None,
+        }
+    }
+}
+
+impl HttpCache {
+    pub(crate) fn new(dir: PathBuf) -> HttpCache {
+        HttpCache { dir }
+    }
+
+    /// Returns the cached response of **url**, fresh or not.
+    pub(crate) fn load(&self, url: &str) -> Option<CacheEntry> {
+        let (head, body) = self.paths(url);
+        let head = fs::read_to_string(head).ok()?;
+        let mut lines = head.lines();
+        let status = lines.next()?.parse().ok()?;
+        let expires_at = UNIX_EPOCH + Duration::from_secs(lines.next()?.parse().ok()?);
+        let etag = Some(lines.next()?.to_string()).filter(|etag| !etag.is_empty());
+        let headers = lines.map(|line| format!("{}\r\n", line)).collect();
+        Some(CacheEntry {
+            status,
+            expires_at,
+            etag,
+            headers,
+            body: fs::read(body).ok()?,
+        })
+    }
+
+    /// Store the successful **response** of **url** received at **now** if the server allows it.
+    pub(crate) fn store(&self, url: &str, response: &Response, now: SystemTime) -> io::Result<()> {
+        if response.status() != 200 {
+            return Ok(());
+        }
+        let expires_at = match expiry(response, now) {
+            Some(expires_at) => expires_at,
+            None => return self.remove(url),
+        };
+        let etag = response.header("ETag").map(String::from);
+        if expires_at <= now && etag.is_none() {
+            return self.remove(url);
+        }
+        self.write(
+            url,
+            &CacheEntry {
+                status: response.status(),
+                expires_at,
+                etag,
+                headers: response.headers().to_vec(),
+                body: response.body().to_vec(),
+            },
+        )
+    }
+
+    /// Update the expiry of the **entry** after the server answered `304 Not Modified`
+    /// with **revalidation** at **now**.
+    pub(crate) fn refresh(
+        &self,
+        url: &str,
+        entry: &mut CacheEntry,
+        revalidation: &Response,
+        now: SystemTime,
+    ) -> io::Result<()> {
+        match expiry(revalidation, now) {
+            Some(expires_at) => {
+                entry.expires_at = expires_at;
+                self.write(url, entry)
+            }
+            None => self.remove(url),
+        }
+    }
+
+    fn write(&self, url: &str, entry: &CacheEntry) -> io::Result<()> {
+        let (head_path, body_path) = self.paths(url);
+        let expires_at = entry
+            .expires_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut head = format!(
+            "{}\n{}\n{}\n",
+            entry.status,
+            expires_at,
+            entry.etag.as_deref().unwrap_or("")
+        );
+        for line in &entry.headers {
+            let line = line.trim_end();
+            if !line.is_empty() {
+                head.push_str(line);
+                head.push('\n');
+            }
+        }
+        fs::create_dir_all(&self.dir)?;
+        write_private(&body_path, &entry.body)?;
+        write_private(&head_path, head.as_bytes())
+    }
+
+    fn remove(&self, url: &str) -> io::Result<()> {
+        let (head, body) = self.paths(url);
+        for path in &[head, body] {
+            match fs::remove_file(path) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn paths(&self, url: &str) -> (PathBuf, PathBuf) {
+        let key = format!("{:016x}", fnv1a(url.as_bytes()));
+        (
+            self.dir.join(format!("{}.head", key)),
+            self.dir.join(format!("{}.body", key)),
+        )
+    }
+}
+
+/// Create or truncate the file at **path** so that only the user may read it,
+/// e.g. for files holding tokens or response bodies.
+pub fn create_private_file(path: &Path) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let file = options.open(path)?;
+    // The mode only applies to new files, not to ones written by an older version.
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    Ok(file)
+}
+
+/// Write **data** to the file at **path** which only the user may read, because
+/// the bodies may contain tokens.
+fn write_private(path: &Path, data: &[u8]) -> io::Result<()> {
+    create_private_file(path)?.write_all(data)
+}
+
+/// Until when the **response** received at **now** may be used from the cache,
+/// `None` if it must not be stored at all.
+fn expiry(response: &Response, now: SystemTime) -> Option<SystemTime> {
+    if let Some(cache_control) = response.header("Cache-Control") {
+        let mut max_age = None;
+        for directive in cache_control.split(',') {
+            let directive = directive.trim().to_ascii_lowercase();
+            match directive.split_once('=') {
+                Some(("max-age", seconds)) => {
+                    max_age = seconds.trim_matches('"').parse().ok();
+                }
+                None if directive == "no-store" => return None,
+                None if directive == "no-cache" => max_age = Some(0),
+                _ => {}
+            }
+        }
+        if let Some(seconds) = max_age {
+            return Some(now + Duration::from_secs(seconds));
+        }
+    }
+    Some(
+        response
+            .header("Expires")
+            .and_then(parse_http_date)
+            .unwrap_or(now),
+    )
+}
+
+/// The FNV-1a hash, which unlike the std hasher is stable across Rust versions.
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(headers: &[&str]) -> Response {
+        let mut lines = vec!["HTTP/1.1 200 OK\r\n".to_string()];
+        lines.extend(headers.iter().map(|header| format!("{}\r\n", header)));
+        Response {
+            url: String::from("https://example.com/a"),
+            status: 200,
+            headers: lines,
+            body: Some(b"body".to_vec()),
+            transfer_length: 4,
+            content_length: 4,
+            bytes_sent: 0,
+            bytes_received: 0,
+            sent_request_id: None,
+        }
+    }
+
+    #[test]
+    fn test_expiry_follows_cache_control() {
+        let now = SystemTime::now();
+        let expiry = |headers| expiry(&response(headers), now);
+        assert_eq!(
+            expiry(&["Cache-Control: public, max-age=60"]),
+            Some(now + Duration::from_secs(60))
+        );
+        assert!(expiry(&["Cache-Control: no-store"]).is_none());
+        assert_eq!(
+            expiry(&[
+                "Cache-Control: no-cache",
+                "Expires: Fri, 01 Jan 2100 00:00:00 GMT"
+            ]),
+            Some(now)
+        );
+
+        let expires = expiry(&["Expires: Fri, 01 Jan 2100 00:00:00 GMT"]).unwrap();
+        assert_eq!(expires, UNIX_EPOCH + Duration::from_secs(4_102_444_800));
+    }
+
+    #[test]
+    fn test_cache_round_trip() {
+        let dir = std::env::temp_dir().join(format!("curl-http-cache-{}", std::process::id()));
+        let cache = HttpCache::new(dir.clone());
+        let url = "https://example.com/a?access_token=secret";
+
+        let now = SystemTime::now();
+        cache
+            .store(
+                url,
+                &response(&["Cache-Control: max-age=60", "ETag: \"v1\""]),
+                now,
+            )
+            .unwrap();
+        let entry = cache.load(url).unwrap();
+        assert!(entry.is_fresh(now));
+        assert!(!entry.is_fresh(now + Duration::from_secs(61)));
+        assert_eq!(entry.etag.as_deref(), Some("\"v1\""));
+        let cached = entry.to_response(url);
+        assert_eq!(cached.body(), b"body");
+        assert_eq!(cached.header("etag"), Some("\"v1\""));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            for path in &[cache.paths(url).0, cache.paths(url).1] {
+                let mode = fs::metadata(path).unwrap().permissions().mode();
+                assert_eq!(mode & 0o777, 0o600);
+            }
+        }
+
+        cache
+            .store(url, &response(&["Cache-Control: no-store"]), now)
+            .unwrap();
+        assert!(cache.load(url).is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
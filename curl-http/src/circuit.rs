@@ -0,0 +1,186 @@
+use std::cell::Cell;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Stops sending requests for a while after several transfers in a row failed,
+/// see [`crate::Client::set_circuit_breaker`].
+pub(crate) struct CircuitBreaker {
+    threshold: u32,
+    window: Duration,
+    cooldown: Duration,
+    failures: Cell<u32>,
+    last_failure: Cell<Option<SystemTime>>,
+    open_until: Cell<Option<SystemTime>>,
+    file: Option<PathBuf>,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(threshold: u32, window: Duration, cooldown: Duration) -> CircuitBreaker {
+        CircuitBreaker {
+            threshold: threshold.max(1),
+            window,
+            cooldown,
+            failures: Cell::new(0),
+            last_failure: Cell::new(None),
+            open_until: Cell::new(None),
+            file: None,
+        }
+    }
+
+    /// Returns a breaker like this one which keeps its state in the **file**,
+    /// starting with the state stored there by an earlier process.
+    pub(crate) fn with_file(&self, file: PathBuf) -> CircuitBreaker {
+        let breaker = CircuitBreaker::new(self.threshold, self.window, self.cooldown);
+        // A missing or broken file counts as a closed circuit.
+        let state = fs::read_to_string(&file).unwrap_or_default();
+        let mut values = state.split_whitespace().map(|value| value.parse::<u64>());
+        if let (Some(Ok(failures)), Some(Ok(last_failure)), Some(Ok(open_until))) =
+            (values.next(), values.next(), values.next())
+        {
+            let time = |secs| Some(UNIX_EPOCH + Duration::from_secs(secs)).filter(|_| secs > 0);
+            breaker
+                .failures
+                .set(failures.min(u64::from(u32::MAX)) as u32);
+            breaker.last_failure.set(time(last_failure));
+            breaker.open_until.set(time(open_until));
+        }
+        CircuitBreaker {
+            file: Some(file),
+            ..breaker
+        }
+    }
+
+    /// Returns how long requests are still refused at **now**, `None` if one may be sent.
+    ///
+    /// After the cooldown one request is let through to probe the server.
+    pub(crate) fn remaining(&self, now: SystemTime) -> Option<Duration> {
+        let open_until = self.open_until.get()?;
+        match open_until.duration_since(now) {
+            Ok(remaining) if remaining > Duration::from_secs(0) => Some(remaining),
+            _ => {
+                self.open_until.set(None);
+                None
+            }
+        }
+    }
+
+    /// Remember a transfer which reached the server.
+    pub(crate) fn record_success(&self) -> io::Result<()> {
+        self.failures.set(0);
+        self.last_failure.set(None);
+        self.open_until.set(None);
+        self.save()
+    }
+
+    /// Remember a transfer which failed at **now**, a failed probe opens the circuit again.
+    ///
+    /// Failures only add up while each one follows the previous within the window.
+    pub(crate) fn record_failure(&self, now: SystemTime) -> io::Result<()> {
+        let mut failures = self.failures.get();
+        let previous = self.last_failure.replace(Some(now));
+        let expired = previous
+            .is_some_and(|previous| now.duration_since(previous).unwrap_or_default() > self.window);
+        if expired && failures < self.threshold {
+            failures = 0;
+        }
+        let failures = failures.saturating_add(1);
+        self.failures.set(failures);
+        if failures >= self.threshold {
+            self.open_until.set(Some(now + self.cooldown));
+        }
+        self.save()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let file = match self.file {
+            Some(ref file) => file,
+            None => return Ok(()),
+        };
+        let secs = |time: Option<SystemTime>| {
+            time.and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |duration| duration.as_secs())
+        };
+        let state = format!(
+            "{} {} {}\n",
+            self.failures.get(),
+            secs(self.last_failure.get()),
+            secs(self.open_until.get())
+        );
+        fs::write(file, state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_circuit_breaker() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60), Duration::from_secs(10));
+        let now = SystemTime::now();
+        breaker.record_failure(now).unwrap();
+        assert_eq!(breaker.remaining(now), None);
+        breaker.record_success().unwrap();
+        breaker.record_failure(now).unwrap();
+        assert_eq!(breaker.remaining(now), None);
+
+        breaker.record_failure(now).unwrap();
+        let later = now + Duration::from_secs(4);
+        assert_eq!(breaker.remaining(later), Some(Duration::from_secs(6)));
+
+        // The probe after the cooldown opens the circuit again right away if it fails.
+        let after_cooldown = now + Duration::from_secs(10);
+        assert_eq!(breaker.remaining(after_cooldown), None);
+        breaker.record_failure(after_cooldown).unwrap();
+        assert!(breaker.remaining(after_cooldown).is_some());
+
+        breaker.record_success().unwrap();
+        assert_eq!(breaker.remaining(after_cooldown), None);
+    }
+
+    #[test]
+    fn test_circuit_breaker_window() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60), Duration::from_secs(10));
+        let now = SystemTime::now();
+        breaker.record_failure(now).unwrap();
+        // Too long after the first failure to count together.
+        let later = now + Duration::from_secs(61);
+        breaker.record_failure(later).unwrap();
+        assert_eq!(breaker.remaining(later), None);
+
+        let soon = later + Duration::from_secs(60);
+        breaker.record_failure(soon).unwrap();
+        assert!(breaker.remaining(soon).is_some());
+
+        // A failed probe long after the last failure opens the circuit again.
+        let probe = soon + Duration::from_secs(120);
+        assert_eq!(breaker.remaining(probe), None);
+        breaker.record_failure(probe).unwrap();
+        assert!(breaker.remaining(probe).is_some());
+    }
+
+    #[test]
+    fn test_circuit_breaker_file() {
+        let file = std::env::temp_dir().join(format!("curl-http-circuit-{}", std::process::id()));
+        let _ = fs::remove_file(&file);
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60), Duration::from_secs(10));
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let first = breaker.with_file(file.clone());
+        assert_eq!(first.remaining(now), None);
+        first.record_failure(now).unwrap();
+        first.record_failure(now).unwrap();
+
+        // A later process still knows that the server is down.
+        let second = breaker.with_file(file.clone());
+        assert_eq!(second.remaining(now), Some(Duration::from_secs(10)));
+        let after_cooldown = now + Duration::from_secs(10);
+        second.record_success().unwrap();
+        assert_eq!(
+            breaker.with_file(file.clone()).remaining(after_cooldown),
+            None
+        );
+        fs::remove_file(file).unwrap();
+    }
+}
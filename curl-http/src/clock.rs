@@ -0,0 +1,25 @@
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// The source of the current time and of waiting, which tests replace with a
+/// [`crate::testing::MockClock`], see [`crate::Client::set_clock`].
+pub trait Clock {
+    /// Returns the current time.
+    fn now(&self) -> SystemTime;
+
+    /// Wait for **duration**, e.g. before retrying a request.
+    fn sleep(&self, duration: Duration);
+}
+
+/// The clock of the operating system, used by default.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
@@ -0,0 +1,65 @@
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use crate::{RequestError, Result};
+
+/// A JSON payload wrapped like `{"data": [...], "meta": {"total": 3}}`,
+/// see [`crate::Response::deserialize_data`].
+#[derive(Debug)]
+pub struct Envelope<T> {
+    /// The unwrapped payload.
+    pub data: T,
+    /// The `meta` object, empty if the envelope has none.
+    pub meta: Map<String, Value>,
+}
+
+impl<T> Envelope<T> {
+    /// Returns the `meta.total` count, e.g. of all items across the pages.
+    pub fn total(&self) -> Option<u64> {
+        self.meta.get("total").and_then(Value::as_u64)
+    }
+
+    /// Returns the `meta` field **name**, e.g. page info.
+    pub fn meta(&self, name: &str) -> Option<&Value> {
+        self.meta.get(name)
+    }
+}
+
+/// Parse the enveloped JSON **body**, which must be an object with a `data` field.
+pub(crate) fn parse<T: DeserializeOwned>(body: &[u8]) -> Result<Envelope<T>> {
+    let mut envelope = match serde_json::from_slice(body) {
+        Ok(Value::Object(envelope)) => envelope,
+        _ => return Err(RequestError::InvalidJson),
+    };
+    let data = envelope.remove("data").ok_or(RequestError::InvalidJson)?;
+    let meta = match envelope.remove("meta") {
+        Some(Value::Object(meta)) => meta,
+        Some(Value::Null) | None => Map::new(),
+        Some(_) => return Err(RequestError::InvalidJson),
+    };
+    Ok(Envelope {
+        data: serde_json::from_value(data).map_err(|_| RequestError::InvalidJson)?,
+        meta,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_envelope() {
+        let envelope: Envelope<Vec<String>> =
+            parse(br#"{"data": ["a", "b"], "meta": {"total": 7, "page": {"next": 2}}}"#).unwrap();
+        assert_eq!(envelope.data, vec!["a", "b"]);
+        assert_eq!(envelope.total(), Some(7));
+        assert_eq!(envelope.meta("page").unwrap()["next"], 2);
+
+        let envelope: Envelope<Vec<String>> = parse(br#"{"data": []}"#).unwrap();
+        assert_eq!(envelope.total(), None);
+
+        assert!(parse::<Vec<String>>(br#"["a", "b"]"#).is_err());
+        assert!(parse::<Vec<String>>(br#"{"data": "a"}"#).is_err());
+        assert!(parse::<Vec<String>>(br#"{"data": [], "meta": 1}"#).is_err());
+    }
+}
@@ -0,0 +1,186 @@
+use crate::url;
+
+/// Extract the file name from a `Content-Disposition` header value, preferring the
+/// extended `filename*=UTF-8''...` form over the plain `filename="..."` one.
+///
+/// The result is sanitized with [`sanitize_filename`], so it is safe to join to a directory.
+pub(crate) fn disposition_filename(value: &str) -> Option<String> {
+    let mut filename = None;
+    let mut extended = None;
+    for param in split_params(value).into_iter().skip(1) {
+        let (key, value) = match param.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => continue,
+        };
+        if key.eq_ignore_ascii_case("filename*") {
+            // charset'language'percent-encoded-value, only UTF-8 is widely used.
+            let mut parts = value.splitn(3, '\'');
+            let charset = parts.next().unwrap_or("");
+            if let (Some(_language), Some(encoded)) = (parts.next(), parts.next()) {
+                if charset.eq_ignore_ascii_case("UTF-8") {
+                    extended = Some(url::percent_decode(encoded).into_owned());
+                }
+            }
+        } else if key.eq_ignore_ascii_case("filename") {
+            filename = Some(unquote(value));
+        }
+    }
+    extended
+        .or(filename)
+        .and_then(|name| sanitize_filename(&name))
+}
+
+/// Reduce a file name suggested by the server to its last path component, so it
+/// can not point outside of the target directory. Returns `None` if nothing usable is left.
+pub(crate) fn sanitize_filename(name: &str) -> Option<String> {
+    let name = name.rsplit(['/', '\\']).next()?.trim();
+    if name.is_empty() || name == "." || name == ".." || name.chars().any(char::is_control) {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+/// Split a header value at the `;` which are not within a quoted string.
+fn split_params(value: &str) -> Vec<&str> {
+    let mut params = vec![];
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (index, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                params.push(value[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    params.push(value[start..].trim());
+    params
+}
+
+/// Remove the quotes and backslash escapes of a quoted string.
+fn unquote(value: &str) -> String {
+    match value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    {
+        Some(inner) => {
+            let mut result = String::with_capacity(inner.len());
+            let mut chars = inner.chars();
+            while let Some(c) = chars.next() {
+                if c == '\\' {
+                    result.extend(chars.next());
+                } else {
+                    result.push(c);
+                }
+            }
+            result
+        }
+        None => value.to_string(),
+    }
+}
+
+/// Header name parts which are not written in Title-Case.
+const ACRONYMS: &[&str] = &[
+    "CH",
+    "CSP",
+    "DNT",
+    "ETag",
+    "MD5",
+    "TE",
+    "UA",
+    "WWW",
+    "WebSocket",
+    "XSS",
+];
+
+/// Returns the header **name** in Title-Case, with the usual spelling of acronyms
+/// like `ETag` or `WWW-Authenticate`.
+pub(crate) fn canonical_name(name: &str) -> String {
+    name.split('-')
+        .map(|part| {
+            if let Some(acronym) = ACRONYMS
+                .iter()
+                .find(|acronym| acronym.eq_ignore_ascii_case(part))
+            {
+                return acronym.to_string();
+            }
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => {
+                    first.to_ascii_uppercase().to_string() + &chars.as_str().to_ascii_lowercase()
+                }
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disposition_filename() {
+        let filename = |value| disposition_filename(value);
+        assert_eq!(
+            filename(r#"attachment; filename="shortcuts-2024.json""#).as_deref(),
+            Some("shortcuts-2024.json")
+        );
+        assert_eq!(
+            filename("attachment; filename=plain.json").as_deref(),
+            Some("plain.json")
+        );
+        assert_eq!(
+            filename(r#"attachment; filename="a \"b\"; c.json""#).as_deref(),
+            Some(r#"a "b"; c.json"#)
+        );
+        assert_eq!(
+            filename(
+                r#"attachment; filename="fallback.json"; filename*=UTF-8''caf%C3%A9%20list.json"#
+            )
+            .as_deref(),
+            Some("caf\u{e9} list.json")
+        );
+        assert_eq!(filename("attachment"), None);
+        assert_eq!(filename("inline; name=x"), None);
+    }
+
+    #[test]
+    fn test_disposition_filename_path_traversal() {
+        let filename = |value| disposition_filename(value);
+        assert_eq!(
+            filename(r#"attachment; filename="../../.bashrc""#).as_deref(),
+            Some(".bashrc")
+        );
+        assert_eq!(
+            filename(r#"attachment; filename=..\..\evil.dll"#).as_deref(),
+            Some("evil.dll")
+        );
+        assert_eq!(
+            filename("attachment; filename*=UTF-8''..%2F..%2Fetc%2Fpasswd").as_deref(),
+            Some("passwd")
+        );
+        assert_eq!(filename(r#"attachment; filename="..""#), None);
+        assert_eq!(filename(r#"attachment; filename="dir/""#), None);
+    }
+
+    #[test]
+    fn test_canonical_name() {
+        assert_eq!(canonical_name("content-type"), "Content-Type");
+        assert_eq!(canonical_name("CONTENT-LENGTH"), "Content-Length");
+        assert_eq!(canonical_name("etag"), "ETag");
+        assert_eq!(canonical_name("www-authenticate"), "WWW-Authenticate");
+        assert_eq!(
+            canonical_name("sec-websocket-accept"),
+            "Sec-WebSocket-Accept"
+        );
+        assert_eq!(canonical_name("x-request-id"), "X-Request-Id");
+    }
+}
@@ -0,0 +1,350 @@
+use serde_json::Value;
+
+use crate::{RequestError, Result};
+
+/// Picks the values matching some patterns out of a JSON document which arrives
+/// in chunks, see [`crate::Request::send_json_values`].
+///
+/// Only the bytes of the value being matched are buffered, the rest of the document
+/// is scanned for its structure and dropped.
+pub(crate) struct JsonStream<'a> {
+    patterns: Vec<Vec<&'a str>>,
+    stack: Vec<Container>,
+    string: Option<StringState>,
+    in_scalar: bool,
+    /// Whether the top-level value ended, nothing but whitespace may follow.
+    complete: bool,
+    capture: Option<Capture>,
+}
+
+struct Container {
+    is_object: bool,
+    /// The key of the current member of an object, or the index of the current array item.
+    segment: String,
+    index: usize,
+    expect: Expect,
+}
+
+/// What may come next in a container besides whitespace.
+#[derive(Copy, Clone, PartialEq)]
+enum Expect {
+    /// The first key or item, or the end of an empty container.
+    First,
+    /// The key after a `,` in an object.
+    Key,
+    /// The `:` after a key.
+    Colon,
+    /// The value after a `:`, or the item after a `,` in an array.
+    Value,
+    /// A `,` or the end of the container.
+    Separator,
+}
+
+struct StringState {
+    is_key: bool,
+    escaped: bool,
+    raw: Vec<u8>,
+}
+
+struct Capture {
+    pattern: usize,
+    depth: usize,
+    bytes: Vec<u8>,
+}
+
+impl<'a> JsonStream<'a> {
+    /// Match values by JSON pointer **patterns** like `/data/items/*`,
+    /// where `*` stands for any key or array index.
+    pub(crate) fn new(patterns: &[&'a str]) -> JsonStream<'a> {
+        JsonStream {
+            patterns: patterns
+                .iter()
+                .map(|pattern| pattern.split('/').skip(1).collect())
+                .collect(),
+            stack: vec![],
+            string: None,
+            in_scalar: false,
+            complete: false,
+            capture: None,
+        }
+    }
+
+    /// Scan the next **chunk** and call **on_value** with the index of the pattern
+    /// and the value for every matching value it completes.
+    pub(crate) fn push(
+        &mut self,
+        chunk: &[u8],
+        on_value: &mut dyn FnMut(usize, Value) -> Result<()>,
+    ) -> Result<()> {
+        for &byte in chunk {
+            self.push_byte(byte, on_value)?;
+        }
+        Ok(())
+    }
+
+    /// Check that the document is complete.
+    pub(crate) fn finish(
+        &mut self,
+        on_value: &mut dyn FnMut(usize, Value) -> Result<()>,
+    ) -> Result<()> {
+        if self.in_scalar {
+            self.in_scalar = false;
+            self.end_value(on_value)?;
+        }
+        if self.string.is_some() || !self.stack.is_empty() {
+            return Err(RequestError::InvalidJson);
+        }
+        Ok(())
+    }
+
+    fn push_byte(
+        &mut self,
+        byte: u8,
+        on_value: &mut dyn FnMut(usize, Value) -> Result<()>,
+    ) -> Result<()> {
+        if self.string.is_some() {
+            self.capture_byte(byte);
+            let mut ends = false;
+            if let Some(ref mut string) = self.string {
+                if string.is_key {
+                    string.raw.push(byte);
+                }
+                if string.escaped {
+                    string.escaped = false;
+                } else if byte == b'\\' {
+                    string.escaped = true;
+                } else {
+                    ends = byte == b'"';
+                }
+            }
+            return if ends {
+                self.end_string(on_value)
+            } else {
+                Ok(())
+            };
+        }
+        if self.in_scalar {
+            if !is_delimiter(byte) {
+                self.capture_byte(byte);
+                return Ok(());
+            }
+            self.in_scalar = false;
+            self.end_value(on_value)?;
+        }
+        if byte.is_ascii_whitespace() {
+            self.capture_byte(byte);
+            return Ok(());
+        }
+
+        match byte {
+            b'{' | b'[' => {
+                self.begin_value()?;
+                self.capture_byte(byte);
+                self.stack.push(Container {
+                    is_object: byte == b'{',
+                    segment: String::from("0"),
+                    index: 0,
+                    expect: Expect::First,
+                });
+            }
+            b'}' | b']' => {
+                match self.stack.pop() {
+                    Some(container)
+                        if container.is_object == (byte == b'}')
+                            && matches!(container.expect, Expect::First | Expect::Separator) => {}
+                    _ => return Err(RequestError::InvalidJson),
+                }
+                self.capture_byte(byte);
+                self.end_value(on_value)?;
+            }
+            b'"' => {
+                let is_key = self.stack.last().is_some_and(|container| {
+                    container.is_object && matches!(container.expect, Expect::First | Expect::Key)
+                });
+                if !is_key {
+                    self.begin_value()?;
+                }
+                self.capture_byte(byte);
+                self.string = Some(StringState {
+                    is_key,
+                    escaped: false,
+                    raw: if is_key { vec![byte] } else { vec![] },
+                });
+            }
+            b':' => {
+                match self.stack.last_mut() {
+                    Some(container) if container.expect == Expect::Colon => {
+                        container.expect = Expect::Value
+                    }
+                    _ => return Err(RequestError::InvalidJson),
+                }
+                self.capture_byte(byte);
+            }
+            b',' => {
+                match self.stack.last_mut() {
+                    Some(container) if container.expect != Expect::Separator => {
+                        return Err(RequestError::InvalidJson)
+                    }
+                    Some(container) if container.is_object => container.expect = Expect::Key,
+                    Some(container) => {
+                        container.index += 1;
+                        container.segment = container.index.to_string();
+                        container.expect = Expect::Value;
+                    }
+                    None => return Err(RequestError::InvalidJson),
+                }
+                self.capture_byte(byte);
+            }
+            _ => {
+                self.begin_value()?;
+                self.capture_byte(byte);
+                self.in_scalar = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Start capturing if the value which begins now matches a pattern.
+    fn begin_value(&mut self) -> Result<()> {
+        let allowed = match self.stack.last() {
+            Some(container) if container.is_object => container.expect == Expect::Value,
+            Some(container) => matches!(container.expect, Expect::First | Expect::Value),
+            None => !self.complete,
+        };
+        if !allowed {
+            return Err(RequestError::InvalidJson);
+        }
+        if self.capture.is_some() {
+            return Ok(());
+        }
+        let pattern = self.patterns.iter().position(|pattern| {
+            pattern.len() == self.stack.len()
+                && pattern
+                    .iter()
+                    .zip(&self.stack)
+                    .all(|(&segment, container)| segment == "*" || segment == container.segment)
+        });
+        if let Some(pattern) = pattern {
+            self.capture = Some(Capture {
+                pattern,
+                depth: self.stack.len(),
+                bytes: vec![],
+            });
+        }
+        Ok(())
+    }
+
+    fn end_string(&mut self, on_value: &mut dyn FnMut(usize, Value) -> Result<()>) -> Result<()> {
+        let string = match self.string.take() {
+            Some(string) => string,
+            None => return Ok(()),
+        };
+        if !string.is_key {
+            return self.end_value(on_value);
+        }
+        let key = serde_json::from_slice(&string.raw).map_err(|_| RequestError::InvalidJson)?;
+        if let Some(container) = self.stack.last_mut() {
+            container.segment = key;
+            container.expect = Expect::Colon;
+        }
+        Ok(())
+    }
+
+    /// Emit the captured value if it ends now.
+    fn end_value(&mut self, on_value: &mut dyn FnMut(usize, Value) -> Result<()>) -> Result<()> {
+        match self.stack.last_mut() {
+            Some(container) => container.expect = Expect::Separator,
+            None => self.complete = true,
+        }
+        if self
+            .capture
+            .as_ref()
+            .is_some_and(|capture| capture.depth == self.stack.len())
+        {
+            if let Some(capture) = self.capture.take() {
+                let value = serde_json::from_slice(&capture.bytes)
+                    .map_err(|_| RequestError::InvalidJson)?;
+                on_value(capture.pattern, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn capture_byte(&mut self, byte: u8) {
+        if let Some(ref mut capture) = self.capture {
+            capture.bytes.push(byte);
+        }
+    }
+}
+
+fn is_delimiter(byte: u8) -> bool {
+    byte.is_ascii_whitespace() || matches!(byte, b',' | b']' | b'}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(patterns: &[&str], chunks: &[&str]) -> Result<Vec<(usize, Value)>> {
+        let mut values = vec![];
+        let mut stream = JsonStream::new(patterns);
+        let mut on_value = |pattern, value| {
+            values.push((pattern, value));
+            Ok(())
+        };
+        for chunk in chunks {
+            stream.push(chunk.as_bytes(), &mut on_value)?;
+        }
+        stream.finish(&mut on_value)?;
+        Ok(values)
+    }
+
+    #[test]
+    fn test_json_stream_matches_patterns() {
+        let body = r#"{"code": 200, "data": {"primary": [{"key": "g", "title": "a \"}]"},
+            {"key": "m"}], "secondary": {"google.com": [{"key": "d"}], "b.com": []}},
+            "message": "ok"}"#;
+        let patterns = ["/code", "/data/primary/*", "/data/secondary/*/*"];
+        let expected = vec![
+            (0, serde_json::json!(200)),
+            (1, serde_json::json!({"key": "g", "title": "a \"}]"})),
+            (1, serde_json::json!({"key": "m"})),
+            (2, serde_json::json!({"key": "d"})),
+        ];
+        assert_eq!(values(&patterns, &[body]).unwrap(), expected);
+
+        // Chunks may split the document at any byte.
+        let chunks: Vec<String> = body.chars().map(String::from).collect();
+        let chunks: Vec<&str> = chunks.iter().map(String::as_str).collect();
+        assert_eq!(values(&patterns, &chunks).unwrap(), expected);
+
+        assert_eq!(
+            values(&["/*"], &["[1, true,null ,", "\"x\"]"]).unwrap(),
+            vec![
+                (0, serde_json::json!(1)),
+                (0, serde_json::json!(true)),
+                (0, serde_json::json!(null)),
+                (0, serde_json::json!("x")),
+            ]
+        );
+        assert_eq!(
+            values(&["/0"], &["[[1, 2], 3]"]).unwrap()[0].1,
+            serde_json::json!([1, 2])
+        );
+    }
+
+    #[test]
+    fn test_json_stream_rejects_broken_documents() {
+        assert!(values(&["/*"], &["[1, 2"]).is_err());
+        assert!(values(&["/*"], &["[1, 2}"]).is_err());
+        assert!(values(&["/*"], &["[1, tru]"]).is_err());
+        assert!(values(&["/a"], &["{1: 2}"]).is_err());
+        // Values without a separator.
+        assert!(values(&["/*"], &["[1 2]"]).is_err());
+        assert!(values(&["/a"], &[r#"{"a" 1}"#]).is_err());
+        assert!(values(&["/a"], &[r#"{"a": 1 "b": 2}"#]).is_err());
+        assert!(values(&["/*"], &["[1, 2,]"]).is_err());
+        assert!(values(&["/*"], &["[, 1]"]).is_err());
+        assert!(values(&["/a"], &[r#"{"a": 1}{"a": 2}"#]).is_err());
+    }
+}
@@ -1,22 +1,144 @@
-use std::cell::{RefCell, RefMut};
+use std::cell::{Cell, RefCell, RefMut};
 use std::fmt;
-use std::io::{Read, Write};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime};
 
+use curl::easy::ReadError;
 use serde::de::DeserializeOwned;
 use serde::{self, Serialize};
 use thiserror::Error;
 
+mod cache;
+mod circuit;
+mod clock;
+mod date;
+mod envelope;
+mod headers;
+mod json_stream;
+mod options;
+pub mod redact;
+pub mod testing;
+mod timeout;
+mod trace;
+mod url;
+
+pub use crate::cache::create_private_file;
+pub use crate::clock::{Clock, SystemClock};
+pub use crate::envelope::Envelope;
+pub use crate::url::{percent_decode, percent_encode};
+
+use crate::cache::HttpCache;
+use crate::circuit::CircuitBreaker;
+use crate::json_stream::JsonStream;
+use crate::options::CurlOption;
+use crate::timeout::AdaptiveTimeout;
+
 /// Shortcut alias for results of this module.
 pub type Result<T> = std::result::Result<T, RequestError>;
 
+/// The wait before the first retry, see [`Client::set_retries`].
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// Headers which may only be sent once, setting them again replaces the previous value.
+const SINGLE_VALUED_HEADERS: &[&str] = &[
+    "Authorization",
+    "Content-Length",
+    "Content-Type",
+    "Host",
+    "Origin",
+    "Referer",
+    "User-Agent",
+    "X-Request-Id",
+    "traceparent",
+];
+
+/// Versions and features of the linked libcurl, for bug reports.
+#[derive(Debug, Clone)]
+pub struct CurlInfo {
+    pub version: String,
+    /// The TLS backend with its version, e.g. `OpenSSL/3.0.2`.
+    pub ssl_version: Option<String>,
+    pub features: Vec<&'static str>,
+}
+
+/// Returns the versions and features of the linked libcurl.
+pub fn curl_info() -> CurlInfo {
+    let version = curl::Version::get();
+    let features = [
+        ("ipv6", version.feature_ipv6()),
+        ("ssl", version.feature_ssl()),
+        ("libz", version.feature_libz()),
+        ("brotli", version.feature_brotli()),
+        ("zstd", version.feature_zstd()),
+        ("http2", version.feature_http2()),
+        ("http3", version.feature_http3()),
+        ("idn", version.feature_idn()),
+        ("async-dns", version.feature_async_dns()),
+        ("https-proxy", version.feature_https_proxy()),
+        ("unix-sockets", version.feature_unix_domain_socket()),
+    ];
+    CurlInfo {
+        version: version.version().to_string(),
+        ssl_version: version.ssl_version().map(String::from),
+        features: features
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| *name)
+            .collect(),
+    }
+}
+
 /// A enum represents HTTP methods.
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Clone, Debug)]
 pub enum Method {
     Get,
     Head,
     Post,
     Put,
+    Patch,
     Delete,
+    /// Any other verb, like `PURGE`. It is neither safe nor idempotent,
+    /// unless the request is marked with [`Request::idempotent`].
+    Custom(String),
+}
+
+impl Method {
+    /// Whether the method only retrieves data without changing anything on the server.
+    pub fn is_safe(&self) -> bool {
+        matches!(*self, Method::Get | Method::Head)
+    }
+
+    /// Whether sending the request several times has the same effect as sending it once,
+    /// which makes it safe to retry.
+    pub fn is_idempotent(&self) -> bool {
+        matches!(
+            *self,
+            Method::Get | Method::Head | Method::Put | Method::Delete
+        )
+    }
+}
+
+impl FromStr for Method {
+    type Err = RequestError;
+
+    /// Parse the well-known methods ignoring case, any other valid verb is a [`Method::Custom`].
+    fn from_str(verb: &str) -> Result<Method> {
+        Ok(match verb.to_ascii_uppercase().as_str() {
+            "GET" => Method::Get,
+            "HEAD" => Method::Head,
+            "POST" => Method::Post,
+            "PUT" => Method::Put,
+            "PATCH" => Method::Patch,
+            "DELETE" => Method::Delete,
+            _ if is_token(verb) => Method::Custom(verb.to_string()),
+            _ => return Err(RequestError::InvalidMethod(verb.to_string())),
+        })
+    }
 }
 
 impl fmt::Display for Method {
@@ -26,27 +148,149 @@ impl fmt::Display for Method {
             Method::Head => write!(f, "HEAD"),
             Method::Post => write!(f, "POST"),
             Method::Put => write!(f, "PUT"),
+            Method::Patch => write!(f, "PATCH"),
             Method::Delete => write!(f, "DELETE"),
+            Method::Custom(ref verb) => write!(f, "{}", verb),
+        }
+    }
+}
+
+/// A hook to get notified about the requests sent by a [`Client`].
+pub trait Observer {
+    /// Called once the response of a request has been received.
+    fn on_response(&self, _exchange: &Exchange) {}
+
+    /// Called when the client notices something wrong which does not fail the request.
+    fn on_warning(&self, _warning: &Warning) {}
+}
+
+/// Problems noticed by the client which do not fail the request.
+#[derive(Debug)]
+pub enum Warning {
+    /// The local clock differs from the `Date` reported by the server
+    /// by more than the allowed skew, see [`Client::set_max_clock_skew`].
+    ClockSkew {
+        local: SystemTime,
+        server: SystemTime,
+    },
+    /// A response could not be written to the [`Client::set_http_cache`].
+    CacheWrite(io::Error),
+    /// The state of the circuit breaker could not be written to the
+    /// [`Client::set_circuit_breaker_file`].
+    CircuitBreakerWrite(io::Error),
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Warning::ClockSkew { local, server } => {
+                let (skew, direction) = match local.duration_since(server) {
+                    Ok(skew) => (skew, "ahead of"),
+                    Err(error) => (error.duration(), "behind"),
+                };
+                write!(
+                    f,
+                    "The local clock is {} seconds {} the server clock, \
+                     time based requests may fail. Please check your system time.",
+                    skew.as_secs(),
+                    direction
+                )
+            }
+            Warning::CacheWrite(ref error) => {
+                write!(f, "Could not write the http cache: {}", error)
+            }
+            Warning::CircuitBreakerWrite(ref error) => {
+                write!(f, "Could not write the circuit breaker state: {}", error)
+            }
         }
     }
 }
 
+/// A completed request together with its response, as seen by an [`Observer`].
+pub struct Exchange<'a> {
+    pub method: &'a Method,
+    pub url: &'a str,
+    pub request_headers: &'a [(String, String)],
+    pub request_body: Option<&'a [u8]>,
+    pub response: &'a Response,
+    pub started_at: SystemTime,
+    pub elapsed: Duration,
+}
+
 ///
 /// A Http client base on curl.
 ///
+/// All requests share one curl handle, so a client performs one transfer at a time
+/// and is not `Sync`. Concurrent identical requests, which would need coalescing,
+/// can't happen with it.
+///
+/// Clones are cheap and share the curl handle with its connection pool and cookies,
+/// the adaptive timeout and the observers. Their settings can be changed independently.
+///
+#[derive(Clone)]
 pub struct Client {
-    shared_handle: RefCell<curl::easy::Easy>,
+    shared_handle: Rc<RefCell<curl::easy::Easy>>,
     base_url: String,
     user_agent: String,
+    referer: Option<String>,
+    origin: Option<String>,
+    doh_url: Option<String>,
+    resolve_overrides: Vec<String>,
+    curl_options: Vec<CurlOption>,
+    timeout: Option<Duration>,
+    adaptive_timeout: Option<Rc<AdaptiveTimeout>>,
+    circuit_breaker: Option<Rc<CircuitBreaker>>,
+    retries: u32,
+    retry_if: Option<RetryPredicate>,
+    follow_redirects: bool,
+    cookies: bool,
+    http_cache: Option<HttpCache>,
+    offline: bool,
+    allow_insecure_http: bool,
+    canonical_header_names: bool,
+    max_clock_skew: Duration,
+    accept_compression: bool,
+    max_response_size: Option<u64>,
+    buffer_size: Option<usize>,
+    trace_ids: bool,
+    clock_skew_warned: Rc<Cell<bool>>,
+    last_transfer_bytes: Cell<(u64, u64)>,
+    clock: Rc<dyn Clock>,
+    observers: Vec<Rc<dyn Observer>>,
 }
 
 impl Client {
     /// Initialize a curl http client based on the **base_url**.
     pub fn new(base_url: &str) -> Client {
         Client {
-            shared_handle: RefCell::new(curl::easy::Easy::new()),
+            shared_handle: Rc::new(RefCell::new(curl::easy::Easy::new())),
             base_url: base_url.to_string(),
             user_agent: "curl-http".to_string(),
+            referer: None,
+            origin: None,
+            doh_url: None,
+            resolve_overrides: vec![],
+            curl_options: vec![],
+            timeout: None,
+            adaptive_timeout: None,
+            circuit_breaker: None,
+            retries: 0,
+            retry_if: None,
+            follow_redirects: false,
+            cookies: false,
+            http_cache: None,
+            offline: false,
+            allow_insecure_http: true,
+            canonical_header_names: false,
+            max_clock_skew: Duration::from_secs(5 * 60),
+            accept_compression: false,
+            max_response_size: None,
+            buffer_size: None,
+            trace_ids: false,
+            clock_skew_warned: Rc::new(Cell::new(false)),
+            last_transfer_bytes: Cell::new((0, 0)),
+            clock: Rc::new(SystemClock),
+            observers: vec![],
         }
     }
 
@@ -55,12 +299,258 @@ impl Client {
         self.user_agent = user_agent.to_string();
     }
 
+    /// Set the default Referer header of every request.
+    pub fn set_referer(&mut self, referer: &str) {
+        self.referer = Some(referer.to_string());
+    }
+
+    /// Set the default Origin header of every request.
+    pub fn set_origin(&mut self, origin: &str) {
+        self.origin = Some(origin.to_string());
+    }
+
+    /// Resolve host names with DNS-over-HTTPS through the resolver at **url**.
+    /// Only `https://` resolver urls are accepted.
+    pub fn set_doh_url(&mut self, url: &str) -> Result<()> {
+        let is_https = url
+            .get(..8)
+            .is_some_and(|scheme| scheme.eq_ignore_ascii_case("https://"));
+        if !is_https {
+            return Err(RequestError::InsecureDohUrl(url.to_string()));
+        }
+        self.doh_url = Some(url.to_string());
+        Ok(())
+    }
+
+    /// Connect to **address** for requests to **host** on **port** instead of resolving it,
+    /// e.g. to reach one server of a staging environment behind a shared load balancer.
+    ///
+    /// Unlike [`Request::with_host`] this keeps the url as it is, so TLS sends the right
+    /// server name and checks the certificate against it. IPv6 addresses need brackets.
+    pub fn add_resolve_override(&mut self, host: &str, port: u16, address: &str) {
+        self.resolve_overrides
+            .push(format!("{}:{}:{}", host, port, address));
+    }
+
+    /// Set a curl option which takes a string by its name, for setups where
+    /// the options come from a config file.
+    ///
+    /// Supported are `proxy`, `noproxy`, `interface`, `cainfo`, `capath`,
+    /// `proxy_cainfo`, `unix_socket` and `ssl_cipher_list`, any other name is refused.
+    pub fn set_curl_string_option(&mut self, name: &str, value: &str) -> Result<()> {
+        self.curl_options
+            .push(CurlOption::from_string(name, value)?);
+        Ok(())
+    }
+
+    /// Set a curl option which takes a number by its name, for setups where
+    /// the options come from a config file.
+    ///
+    /// Supported are `timeout_ms`, `connect_timeout_ms`, `low_speed_limit`,
+    /// `low_speed_time` (seconds), `max_redirections`, `ipresolve` (0 any, 1 IPv4, 2 IPv6),
+    /// `tcp_keepalive` and `verbose` (0 or 1), any other name is refused.
+    pub fn set_curl_long_option(&mut self, name: &str, value: i64) -> Result<()> {
+        self.curl_options.push(CurlOption::from_long(name, value)?);
+        Ok(())
+    }
+
+    /// Set the maximum time a whole request may take. Default is no limit.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = Some(timeout);
+    }
+
+    /// Derive the timeout of every request from how long the latest requests took:
+    /// a multiple of their 95th percentile, but at least **min** and at most **max**.
+    ///
+    /// Until the first request finished the timeout is **max**. A request which timed
+    /// out counts as taking the whole timeout, so it grows on a slow network.
+    /// This replaces the fixed [`Client::set_timeout`].
+    pub fn set_adaptive_timeout(&mut self, min: Duration, max: Duration) {
+        self.adaptive_timeout = Some(Rc::new(AdaptiveTimeout::new(min, max)));
+    }
+
+    /// Refuse to send requests for **cooldown** with [`RequestError::CircuitOpen`]
+    /// once **threshold** transfers in a row failed to reach the server, each within
+    /// **window** of the previous one, e.g. to give up quickly while it is down instead
+    /// of waiting for every timeout.
+    ///
+    /// After the cooldown the next request is sent to probe the server: the circuit
+    /// closes if it gets through and opens again if it doesn't. The state is shared
+    /// with the clones of the client.
+    pub fn set_circuit_breaker(&mut self, threshold: u32, window: Duration, cooldown: Duration) {
+        self.circuit_breaker = Some(Rc::new(CircuitBreaker::new(threshold, window, cooldown)));
+    }
+
+    /// Keep the state of the [`Client::set_circuit_breaker`] in the **file**, so that
+    /// later processes know that the server is down as well. Must be called after it.
+    pub fn set_circuit_breaker_file(&mut self, file: impl Into<PathBuf>) {
+        if let Some(ref breaker) = self.circuit_breaker {
+            self.circuit_breaker = Some(Rc::new(breaker.with_file(file.into())));
+        }
+    }
+
+    /// Set how often [`Request::send`] retries an idempotent request after a
+    /// connection failure, a timeout or a 502, 503 or 504 response.
+    /// The wait between the attempts doubles, starting at 250ms. Default is 0.
+    pub fn set_retries(&mut self, retries: u32) {
+        self.retries = retries;
+    }
+
+    /// Decide with **retry_if** whether to retry after an attempt instead of the
+    /// default rules, see [`RetryContext::is_retryable`]. It is asked about successful
+    /// responses too, e.g. to retry on an API error code in the body.
+    ///
+    /// The predicate runs after the idempotency gate: requests which are not retried
+    /// at all (not idempotent, streamed body, [`Request::no_retry`]) never reach it,
+    /// and neither does the last of the [`Client::set_retries`] attempts.
+    pub fn set_retry_if(&mut self, retry_if: impl Fn(&RetryContext) -> bool + 'static) {
+        self.retry_if = Some(Rc::new(retry_if));
+    }
+
+    /// Follow `Location` headers of redirect responses. Default is `false`,
+    /// single requests can opt out with [`Request::no_redirect`].
+    pub fn set_follow_redirects(&mut self, follow: bool) {
+        self.follow_redirects = follow;
+    }
+
+    /// Remember the cookies set by the server in memory and send them with later
+    /// requests, also of clones of the client. Default is `false`.
+    pub fn set_cookies(&mut self, cookies: bool) {
+        self.cookies = cookies;
+    }
+
+    /// Cache the responses of GET requests in the directory **dir**.
+    ///
+    /// A response is reused until it expires according to its `Cache-Control: max-age`
+    /// or `Expires` header, and then revalidated with `If-None-Match` if it had an `ETag`.
+    /// Responses with `Cache-Control: no-store` are never written to disk. The files
+    /// are only readable by the user, and a cache which can't be written is skipped.
+    pub fn set_http_cache(&mut self, dir: impl Into<PathBuf>) {
+        self.http_cache = Some(HttpCache::new(dir.into()));
+    }
+
+    /// Never touch the network, requests fail with [`RequestError::Offline`].
+    ///
+    /// GET requests are still answered from the [`Client::set_http_cache`],
+    /// even when the cached response has expired. Default is `false`.
+    pub fn set_offline(&mut self, offline: bool) {
+        self.offline = offline;
+    }
+
+    /// Whether requests to non-https urls of other hosts than the local machine are
+    /// allowed. Default is `true`; refuse them with [`RequestError::InsecureUrl`]
+    /// when credentials like access tokens must not be readable on the network.
+    pub fn set_allow_insecure_http(&mut self, allow: bool) {
+        self.allow_insecure_http = allow;
+    }
+
+    /// Rewrite the names of response headers to Title-Case, e.g. `content-type` to
+    /// `Content-Type` and `etag` to `ETag`, so they look the same for every server.
+    /// The values are kept as they are. Default is `false`.
+    pub fn set_canonical_header_names(&mut self, canonical: bool) {
+        self.canonical_header_names = canonical;
+    }
+
+    /// Ask for compressed responses with every encoding libcurl supports, which
+    /// are decoded transparently. Default is `false`.
+    pub fn set_accept_compression(&mut self, accept: bool) {
+        self.accept_compression = accept;
+    }
+
+    /// Abort requests whose body is larger than **bytes** after decoding,
+    /// so a small compressed response can't expand into an unlimited one.
+    pub fn set_max_response_size(&mut self, bytes: u64) {
+        self.max_response_size = Some(bytes);
+    }
+
+    /// Let curl receive up to **bytes** per write instead of its default 16 KiB,
+    /// which saves calls for large downloads over fast links. Curl keeps the size
+    /// between 1 KiB and 10 MiB.
+    pub fn set_buffer_size(&mut self, bytes: usize) {
+        self.buffer_size = Some(bytes);
+    }
+
+    /// Set how far the local clock may drift from the server `Date` header
+    /// before observers get a [`Warning::ClockSkew`]. Default is 5 minutes.
+    pub fn set_max_clock_skew(&mut self, skew: Duration) {
+        self.max_clock_skew = skew;
+    }
+
+    /// Returns the bytes sent and received by the latest transfer like
+    /// [`Response::bytes_sent`] and [`Response::bytes_received`], also if it failed,
+    /// e.g. to tell how much of an interrupted download arrived.
+    pub fn last_transfer_bytes(&self) -> (u64, u64) {
+        self.last_transfer_bytes.get()
+    }
+
+    /// Returns the skew allowed by [`Client::set_max_clock_skew`].
+    pub fn max_clock_skew(&self) -> Duration {
+        self.max_clock_skew
+    }
+
+    /// Take the time from **clock** for retry delays, cache expiry and the clock
+    /// skew check instead of the [`SystemClock`], e.g. a [`testing::MockClock`].
+    pub fn set_clock(&mut self, clock: Rc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Send a new random request id with every request, see [`Request::with_trace_id`].
+    /// Default is `false`.
+    pub fn set_trace_ids(&mut self, trace_ids: bool) {
+        self.trace_ids = trace_ids;
+    }
+
+    /// Register an observer which gets notified about every response.
+    pub fn add_observer(&mut self, observer: Rc<dyn Observer>) {
+        self.observers.push(observer);
+    }
+
     /// Make a specific method request.
-    pub fn request(&self, method: Method, endpoint: &str) -> Result<Request> {
-        let url = format!("{}{}", self.base_url, endpoint);
-        let mut handle = self.shared_handle.borrow_mut();
-        handle.reset();
-        Request::new(handle, method, &url)?.with_user_agent(&self.user_agent)
+    pub fn request(&self, method: Method, endpoint: &str) -> Result<Request<'_>> {
+        self.request_url(method, &format!("{}{}", self.base_url, endpoint))
+    }
+
+    /// Send a request made with [`Request::build`], like [`Request::send`] would have.
+    ///
+    /// Only the method, url and headers are checked again, e.g. against [`Client::set_offline`].
+    pub fn send_prepared(&self, prepared: PreparedRequest) -> Result<Response> {
+        Request::from_prepared(self, prepared)?.send()
+    }
+
+    /// Make a request to an absolute **url**.
+    fn request_url(&self, method: Method, url: &str) -> Result<Request<'_>> {
+        let mut request = Request::new(self, method, url)?.with_user_agent(&self.user_agent)?;
+        if let Some(ref referer) = self.referer {
+            request = request.with_referer(referer)?;
+        }
+        if let Some(ref origin) = self.origin {
+            request = request.with_origin(origin)?;
+        }
+        Ok(request)
+    }
+
+    /// Warn the observers once if the server clock differs too much from ours.
+    fn check_clock_skew(&self, response: &Response) {
+        if self.clock_skew_warned.get() {
+            return;
+        }
+        if let Some(server) = response.server_date() {
+            let local = self.clock.now();
+            let skew = match local.duration_since(server) {
+                Ok(skew) => skew,
+                Err(error) => error.duration(),
+            };
+            if skew > self.max_clock_skew {
+                self.clock_skew_warned.set(true);
+                self.warn(&Warning::ClockSkew { local, server });
+            }
+        }
+    }
+
+    fn warn(&self, warning: &Warning) {
+        for observer in &self.observers {
+            observer.on_warning(warning);
+        }
     }
 
     /// High level HTTP **GET** method
@@ -68,6 +558,100 @@ impl Client {
         self.request(Method::Get, endpoint)?.send()
     }
 
+    /// Whether the resource at **endpoint** exists, checked with a **HEAD** request:
+    /// `true` for a `2xx`, `false` for a `404` or `410` response.
+    ///
+    /// Servers which don't allow HEAD (`405` or `501`) are asked with a GET instead,
+    /// any other status is a [`RequestError::RequestFailed`].
+    pub fn exists(&self, endpoint: &str) -> Result<bool> {
+        let mut response = self.request(Method::Head, endpoint)?.send()?;
+        if let 405 | 501 = response.status() {
+            response = self.get(endpoint)?;
+        }
+        match response.status() {
+            200..=299 => Ok(true),
+            404 | 410 => Ok(false),
+            _ => Err(RequestError::RequestFailed),
+        }
+    }
+
+    /// HTTP **GET** method which returns the body of a successful response.
+    pub fn get_bytes(&self, endpoint: &str) -> Result<Vec<u8>> {
+        let response = self.get(endpoint)?;
+        if response.failed() {
+            return Err(RequestError::RequestFailed);
+        }
+        Ok(response.into_body().unwrap_or_default())
+    }
+
+    /// HTTP **GET** method which returns the body of a successful response as UTF-8 text.
+    pub fn get_text(&self, endpoint: &str) -> Result<String> {
+        String::from_utf8(self.get_bytes(endpoint)?).map_err(|_| RequestError::InvalidText)
+    }
+
+    /// HTTP **GET** method which calls **on_line** for every line of the response body
+    /// as it arrives, see [`Request::send_lines`].
+    pub fn stream_lines(&self, endpoint: &str, on_line: impl FnMut(&str)) -> Result<Response> {
+        self.request(Method::Get, endpoint)?.send_lines(on_line)
+    }
+
+    /// Download the resource at **endpoint** into the file at **path**.
+    ///
+    /// If **path** is a directory, the file is named after the `Content-Disposition`
+    /// header of the response, or else after the last segment of the endpoint.
+    /// The body is streamed into a temporary file next to the target, which only
+    /// replaces the target once the download succeeded. Returns the path of the file.
+    pub fn download(&self, endpoint: &str, path: impl AsRef<Path>) -> Result<PathBuf> {
+        let path = path.as_ref();
+        let directory = if path.is_dir() {
+            path
+        } else {
+            path.parent().unwrap_or_else(|| Path::new(""))
+        };
+        let partial_path = directory.join(format!(".curl-http-{}.part", process::id()));
+
+        let request = self.request(Method::Get, endpoint)?;
+        let mut file = File::create(&partial_path)?;
+        let result = request.send_to(&mut file);
+        drop(file);
+        let response = match result {
+            Ok(response) if response.ok() => response,
+            result => {
+                // The error of the request matters more than a leftover partial file.
+                let _ = fs::remove_file(&partial_path);
+                return result.and(Err(RequestError::RequestFailed));
+            }
+        };
+
+        let target = if path.is_dir() {
+            let fallback = endpoint
+                .split(['?', '#'])
+                .next()
+                .and_then(headers::sanitize_filename);
+            path.join(
+                response
+                    .suggested_filename()
+                    .or(fallback)
+                    .unwrap_or_else(|| "download".to_string()),
+            )
+        } else {
+            path.to_path_buf()
+        };
+        fs::rename(&partial_path, &target)?;
+        Ok(target)
+    }
+
+    /// Iterate over the pages of a paginated resource, see [`Pages`].
+    pub fn pages(&self, endpoint: &str) -> Pages<'_> {
+        Pages {
+            client: self,
+            next: Some(format!("{}{}", self.base_url, endpoint)),
+            stop_on_error: true,
+            max_errors: 3,
+            errors: 0,
+        }
+    }
+
     /// High level HTTP **POST** method
     pub fn post<S: Serialize>(&self, endpoint: &str, body: &S) -> Result<Response> {
         self.request(Method::Post, endpoint)?
@@ -82,27 +666,151 @@ impl Client {
             .send()
     }
 
+    /// High level HTTP **PATCH** method
+    pub fn patch<S: Serialize>(&self, endpoint: &str, body: &S) -> Result<Response> {
+        self.request(Method::Patch, endpoint)?
+            .with_json_body(body)?
+            .send()
+    }
+
     /// High level HTTP **DELETE** method
     pub fn delete(&self, endpoint: &str) -> Result<Response> {
         self.request(Method::Delete, endpoint)?.send()
     }
 }
 
+/// An iterator which **GET**s one page after another, following the
+/// `Link: <...>; rel="next"` header of every response until there is none.
+///
+/// Responses with a failed status are returned as they are, the caller decides
+/// what to do with them. Iteration ends after the first transport error
+/// unless [`Pages::stop_on_error`] is turned off, in which case the failed page
+/// is requested again on the next call, until it failed [`Pages::max_errors`] times in a row.
+pub struct Pages<'a> {
+    client: &'a Client,
+    next: Option<String>,
+    stop_on_error: bool,
+    max_errors: u32,
+    errors: u32,
+}
+
+impl<'a> Pages<'a> {
+    /// Whether to stop after the first transport error. Default is `true`.
+    pub fn stop_on_error(mut self, stop: bool) -> Pages<'a> {
+        self.stop_on_error = stop;
+        self
+    }
+
+    /// How often a page may fail in a row before the iteration ends, when
+    /// [`Pages::stop_on_error`] is turned off. Default is 3.
+    pub fn max_errors(mut self, max_errors: u32) -> Pages<'a> {
+        self.max_errors = max_errors;
+        self
+    }
+
+    /// Request the next page like [`Iterator::next`], but pass the values of its body
+    /// which match the **patterns** to **on_value** as they arrive, see
+    /// [`Request::send_json_values`].
+    pub fn next_json_values(
+        &mut self,
+        patterns: &[&str],
+        on_value: impl FnMut(usize, serde_json::Value) -> Result<()>,
+    ) -> Option<Result<Response>> {
+        self.fetch(|request| request.send_json_values(patterns, on_value))
+    }
+
+    fn fetch(
+        &mut self,
+        send: impl FnOnce(Request<'a>) -> Result<Response>,
+    ) -> Option<Result<Response>> {
+        let url = self.next.take()?;
+        match self.client.request_url(Method::Get, &url).and_then(send) {
+            Ok(response) => {
+                self.errors = 0;
+                self.next = response.link("next").map(|link| url::join(&url, link));
+                Some(Ok(response))
+            }
+            Err(error) => {
+                self.errors += 1;
+                if !self.stop_on_error && self.errors < self.max_errors {
+                    self.next = Some(url);
+                }
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+impl Iterator for Pages<'_> {
+    type Item = Result<Response>;
+
+    fn next(&mut self) -> Option<Result<Response>> {
+        self.fetch(Request::send)
+    }
+}
+
 /// The struct represents the HTTP request.
 pub struct Request<'a> {
+    client: &'a Client,
     handle: RefMut<'a, curl::easy::Easy>,
-    headers: curl::easy::List,
+    method: Method,
+    headers: Vec<(String, String)>,
     url: String,
     body: Option<Vec<u8>>,
+    reader: Option<Box<dyn Read + 'a>>,
+    idempotent: bool,
+    retry: bool,
+    follow_redirects: bool,
+    trace_id: Option<String>,
+    /// The status of the response being received, shared with the write callbacks.
+    status: Rc<Cell<HttpStatus>>,
 }
 
 impl<'a> Request<'a> {
-    pub fn new(
-        mut handle: RefMut<'a, curl::easy::Easy>,
-        method: Method,
-        url: &str,
-    ) -> Result<Request<'a>> {
-        match method {
+    /// Prepare a request on the shared handle of the **client**.
+    pub fn new(client: &'a Client, method: Method, url: &str) -> Result<Request<'a>> {
+        let is_https = url::scheme(url).is_some_and(|scheme| scheme.eq_ignore_ascii_case("https"));
+        let is_loopback = url::host(url).is_some_and(url::is_loopback);
+        if !is_https && !is_loopback && !client.allow_insecure_http {
+            return Err(RequestError::InsecureUrl(redact::url(url)));
+        }
+        if client.offline && (method != Method::Get || client.http_cache.is_none()) {
+            return Err(RequestError::Offline(url::path(url).to_string()));
+        }
+
+        let mut handle = client.shared_handle.borrow_mut();
+        handle.reset();
+        if let Some(ref doh_url) = client.doh_url {
+            handle.doh_url(Some(doh_url))?;
+        }
+        // The adaptive timeout is set for every attempt in `timed_transfer`.
+        if let (None, Some(timeout)) = (&client.adaptive_timeout, client.timeout) {
+            handle.timeout(timeout)?;
+        }
+        if client.follow_redirects {
+            handle.follow_location(true)?;
+        }
+        if client.accept_compression {
+            handle.accept_encoding("")?;
+        }
+        if client.cookies {
+            handle.cookie_file("")?;
+        }
+        if let Some(bytes) = client.buffer_size {
+            handle.buffer_size(bytes)?;
+        }
+        if !client.resolve_overrides.is_empty() {
+            let mut resolve = curl::easy::List::new();
+            for entry in &client.resolve_overrides {
+                resolve.append(entry)?;
+            }
+            handle.resolve(resolve)?;
+        }
+        for option in &client.curl_options {
+            option.apply(&mut handle)?;
+        }
+
+        match &method {
             Method::Get => handle.get(true)?,
             Method::Head => {
                 handle.get(true)?;
@@ -111,29 +819,118 @@ impl<'a> Request<'a> {
             }
             Method::Post => handle.custom_request("POST")?,
             Method::Put => handle.custom_request("PUT")?,
+            Method::Patch => handle.custom_request("PATCH")?,
             Method::Delete => handle.custom_request("DELETE")?,
+            Method::Custom(ref verb) => {
+                if !is_token(verb) {
+                    return Err(RequestError::InvalidMethod(verb.clone()));
+                }
+                handle.custom_request(verb)?
+            }
         }
 
-        Ok(Request {
+        let request = Request {
+            client,
             handle,
-            headers: curl::easy::List::new(),
+            idempotent: method.is_idempotent(),
+            retry: true,
+            follow_redirects: client.follow_redirects,
+            method,
+            headers: vec![],
             url: url.to_string(),
             body: None,
-        })
+            reader: None,
+            trace_id: None,
+            status: Rc::new(Cell::new(0)),
+        };
+        if client.trace_ids {
+            return request.with_trace_id(&trace::generate_id());
+        }
+        Ok(request)
     }
 
     /// Set the HTTP header.
+    ///
+    /// Headers which may only be sent once, like `User-Agent` or `Content-Type`,
+    /// replace the previous value instead of being sent twice.
     pub fn with_header(mut self, key: &str, value: &str) -> Result<Request<'a>> {
-        self.headers.append(&format!("{}: {}", key, value))?;
+        if SINGLE_VALUED_HEADERS
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(key))
+        {
+            self.headers
+                .retain(|(name, _)| !name.eq_ignore_ascii_case(key));
+        }
+        self.headers.push((key.to_string(), value.to_string()));
+        Ok(self)
+    }
+
+    /// Set several HTTP headers at once, see [`Request::with_header`].
+    pub fn with_headers(mut self, headers: &[(&str, &str)]) -> Result<Request<'a>> {
+        for (key, value) in headers {
+            self = self.with_header(key, value)?;
+        }
         Ok(self)
     }
 
     /// Set custom User-Agent.
-    pub fn with_user_agent(mut self, ua: &str) -> Result<Request<'a>> {
-        self.headers.append(&format!("User-Agent: {}", ua))?;
+    pub fn with_user_agent(self, ua: &str) -> Result<Request<'a>> {
+        self.with_header("User-Agent", ua)
+    }
+
+    /// Set the Referer header.
+    pub fn with_referer(self, url: &str) -> Result<Request<'a>> {
+        self.with_header("Referer", url)
+    }
+
+    /// Send **host** as the `Host` header while still connecting to the host of the url,
+    /// e.g. to ask an IP address for a specific virtual host.
+    ///
+    /// Only the header changes: with https, TLS still sends the url host as server name
+    /// and checks the certificate against it, use [`Client::add_resolve_override`] there.
+    pub fn with_host(self, host: &str) -> Result<Request<'a>> {
+        self.with_header("Host", host)
+    }
+
+    /// Set the Origin header.
+    pub fn with_origin(self, origin: &str) -> Result<Request<'a>> {
+        self.with_header("Origin", origin)
+    }
+
+    /// Add a preference like `return=minimal` to the `Prefer` header, servers which
+    /// honor it report so in [`Response::preference_applied`].
+    pub fn prefer(mut self, preference: &str) -> Result<Request<'a>> {
+        match self
+            .headers
+            .iter_mut()
+            .find(|(name, _)| name.eq_ignore_ascii_case("Prefer"))
+        {
+            Some((_, value)) => {
+                value.push_str(", ");
+                value.push_str(preference);
+            }
+            None => self
+                .headers
+                .push(("Prefer".to_string(), preference.to_string())),
+        }
         Ok(self)
     }
 
+    /// Send **id** as `X-Request-Id`, so the request can be found in the server logs.
+    /// It is part of the errors of [`Request::send_checked`] and of
+    /// [`Response::request_id`] unless the server returns its own.
+    ///
+    /// With the `tracing` feature the `request` span records the id, and an id of
+    /// 32 lowercase hex digits is sent as the trace id of a W3C `traceparent` too.
+    pub fn with_trace_id(mut self, id: &str) -> Result<Request<'a>> {
+        self.trace_id = Some(id.to_string());
+        #[cfg(feature = "tracing")]
+        if trace::is_trace_id(id) {
+            self = self.with_header("traceparent", &trace::traceparent(id))?;
+        }
+        self.with_header("X-Request-Id", id)
+    }
+
     /// Set custom url arguments or querystring.
     pub fn with_arguments(mut self, args: &str) -> Result<Request<'a>> {
         self.url = format!("{}?{}", self.url, args);
@@ -147,94 +944,859 @@ impl<'a> Request<'a> {
         serde_json::to_writer(&mut body_bytes, &body).map_err(|_| RequestError::InvalidJsonBody)?;
 
         self.body = Some(body_bytes);
-        self.headers.append("Content-Type: application/json")?;
-        Ok(self)
+        self.reader = None;
+        self.with_header("Content-Type", "application/json")
     }
 
-    /// Sends the request and reads the response body into the response object.
-    pub fn send(mut self) -> Result<Response> {
-        self.handle.http_headers(self.headers)?;
-        self.handle.url(&self.url)?;
+    /// Set the raw request **body**, which is sent as is even for a GET request,
+    /// e.g. for search endpoints which take a query in the body.
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Result<Request<'a>> {
+        self.body = Some(body.into());
+        self.reader = None;
+        Ok(self)
+    }
 
-        match self.body {
-            Some(ref body) => {
-                let mut body: &[u8] = &body[..];
-                self.handle.upload(true)?;
-                self.handle.in_filesize(body.len() as u64)?;
-                handle_request(&mut self.handle, &mut |buffer| {
-                    body.read(buffer).unwrap_or(0)
-                })
-            }
-            None => handle_request(&mut self.handle, &mut |_| 0),
-        }
+    /// Stream the request body from the **reader** while sending, e.g. from stdin.
+    ///
+    /// The size is not known upfront, so the body is sent with chunked transfer encoding.
+    /// Observers don't get the streamed body in their [`Exchange`].
+    pub fn with_reader(mut self, reader: impl Read + 'a) -> Result<Request<'a>> {
+        self.reader = Some(Box::new(reader));
+        self.body = None;
+        Ok(self)
     }
-}
 
-fn handle_request(
-    handle: &mut curl::easy::Easy,
-    read: &mut dyn FnMut(&mut [u8]) -> usize,
-) -> Result<Response> {
-    let mut response_body = vec![];
-    let mut response_headers = vec![];
+    /// Mark the request as idempotent or not, which decides whether [`Request::send`]
+    /// may retry it. Defaults to [`Method::is_idempotent`], e.g. to allow retrying
+    /// a custom verb which is known to be idempotent.
+    pub fn idempotent(mut self, idempotent: bool) -> Result<Request<'a>> {
+        self.idempotent = idempotent;
+        Ok(self)
+    }
 
-    {
-        let mut handle = handle.transfer();
+    /// Never retry this request, regardless of [`Client::set_retries`] and
+    /// [`Request::idempotent`], e.g. to observe the first response.
+    pub fn no_retry(mut self) -> Result<Request<'a>> {
+        self.retry = false;
+        Ok(self)
+    }
 
-        handle.read_function(move |buffer| Ok(read(buffer)))?;
+    /// Return a redirect response instead of following it, even if the client
+    /// is set to [`Client::set_follow_redirects`].
+    pub fn no_redirect(mut self) -> Result<Request<'a>> {
+        self.handle.follow_location(false)?;
+        self.follow_redirects = false;
+        Ok(self)
+    }
 
-        handle.write_function(|data| {
-            Ok(match response_body.write_all(data) {
-                Ok(_) => data.len(),
-                Err(_) => 0,
-            })
-        })?;
+    /// Finish the request without sending it, e.g. to look at the headers or to
+    /// send it later with [`Client::send_prepared`]. The url and all headers are
+    /// final, and the method and url have been validated.
+    ///
+    /// Fails with [`RequestError::StreamedBody`] for a body from [`Request::with_reader`],
+    /// which can be read only once.
+    pub fn build(self) -> Result<PreparedRequest> {
+        if self.reader.is_some() {
+            return Err(RequestError::StreamedBody);
+        }
+        Ok(PreparedRequest {
+            method: self.method,
+            url: self.url,
+            headers: self.headers,
+            body: self.body,
+            idempotent: self.idempotent,
+            retry: self.retry,
+            follow_redirects: self.follow_redirects,
+            trace_id: self.trace_id,
+        })
+    }
 
-        handle.header_function(|data| {
-            response_headers.push(String::from_utf8_lossy(data).into_owned());
-            true
-        })?;
-        handle.perform()?;
+    /// Continue with a **prepared** request on the shared handle of the **client**.
+    fn from_prepared(client: &'a Client, prepared: PreparedRequest) -> Result<Request<'a>> {
+        let mut request = Request::new(client, prepared.method, &prepared.url)?;
+        if !prepared.follow_redirects {
+            request = request.no_redirect()?;
+        }
+        request.headers = prepared.headers;
+        request.body = prepared.body;
+        request.idempotent = prepared.idempotent;
+        request.retry = prepared.retry;
+        request.trace_id = prepared.trace_id;
+        Ok(request)
     }
 
-    Ok(Response {
-        status: handle.response_code()?,
-        headers: response_headers,
-        body: Some(response_body),
-    })
-}
+    /// Sends the request and reads the response body into the response object.
+    ///
+    /// Idempotent requests without a streamed body are retried as configured
+    /// with [`Client::set_retries`], GET requests without a body go through the
+    /// [`Client::set_http_cache`].
+    pub fn send(mut self) -> Result<Response> {
+        let client = self.client;
+        let cache = match client.http_cache {
+            Some(ref cache) if self.method == Method::Get && self.body.is_none() => cache,
+            _ => return self.send_uncached(),
+        };
 
-/// Type alias for **u32** http status.
-pub type HttpStatus = u32;
+        let url = self.url.clone();
+        let cached = cache.load(&url);
+        if let Some(ref entry) = cached {
+            if entry.is_fresh(client.clock.now()) || client.offline {
+                return Ok(entry.to_response(&url));
+            }
+            if let Some(ref etag) = entry.etag {
+                self = self.with_header("If-None-Match", etag)?;
+            }
+        }
 
-/// The struct represents the HTTP response.
-#[derive(Clone, Debug)]
-pub struct Response {
-    status: HttpStatus,
-    headers: Vec<String>,
-    body: Option<Vec<u8>>,
-}
+        // The server answered already, a cache which can't be written must not fail the request.
+        let response = self.send_uncached()?;
+        let (written, response) = match cached {
+            Some(mut entry) if response.status() == 304 => (
+                cache.refresh(&url, &mut entry, &response, client.clock.now()),
+                entry.to_response(&url),
+            ),
+            _ => (cache.store(&url, &response, client.clock.now()), response),
+        };
+        if let Err(error) = written {
+            client.warn(&Warning::CacheWrite(error));
+        }
+        Ok(response)
+    }
 
-impl Response {
-    pub fn status(&self) -> HttpStatus {
-        self.status
+    fn send_uncached(mut self) -> Result<Response> {
+        let retries = if self.retry && self.idempotent && self.reader.is_none() {
+            self.client.retries
+        } else {
+            0
+        };
+        let mut attempt = 0;
+        loop {
+            let started_at = SystemTime::now();
+            let timer = Instant::now();
+            let mut response_body = vec![];
+            let result =
+                self.perform(&mut |data| response_body.write_all(data))
+                    .map(|mut response| {
+                        response.body = Some(response_body);
+                        self.notify_observers(&response, started_at, timer.elapsed());
+                        response
+                    });
+            if attempt == retries || !self.should_retry(attempt, &result) {
+                return result;
+            }
+            self.client
+                .clock
+                .sleep(RETRY_DELAY * 2u32.pow(attempt.min(5)));
+            attempt += 1;
+        }
     }
 
-    pub fn failed(&self) -> bool {
-        self.status >= 400 && self.status <= 600
+    fn should_retry(&self, attempt: u32, result: &Result<Response>) -> bool {
+        let context = RetryContext {
+            method: &self.method,
+            attempt,
+            response: result.as_ref().ok(),
+            error: result.as_ref().err(),
+        };
+        match self.client.retry_if {
+            Some(ref retry_if) => retry_if(&context),
+            None => context.is_retryable(),
+        }
     }
 
-    pub fn ok(&self) -> bool {
-        !self.failed()
+    /// Sends the request like [`Request::send`], but turns a `4xx` response into
+    /// [`RequestError::Rejected`], which quotes the response body to show why it was
+    /// refused, and the request body to show what was refused.
+    ///
+    /// The quoted bodies are truncated and secret looking JSON fields are redacted.
+    pub fn send_checked(self) -> Result<Response> {
+        let request_body = self.body.as_deref().map(redact::body_excerpt);
+        let response = self.send()?;
+        if (400..500).contains(&response.status()) {
+            return Err(RequestError::Rejected {
+                status: response.status(),
+                request_id: response.request_id().map(String::from),
+                response_body: Some(response.body())
+                    .filter(|_| !response.is_empty_body())
+                    .map(redact::body_excerpt),
+                request_body,
+            });
+        }
+        Ok(response)
     }
 
-    /// Deserialize the response body into the given type
-    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T> {
-        if self.ok() {
-            Ok(serde_json::from_reader(match self.body {
-                Some(ref body) => body,
-                None => &b""[..],
+    /// Sends the request and deserializes the `data` of the enveloped response,
+    /// see [`Response::deserialize_data`].
+    ///
+    /// Fails with [`RequestError::UnexpectedContentType`] if the response is not JSON,
+    /// e.g. an HTML error page of a proxy.
+    pub fn send_json_data<T: DeserializeOwned>(self) -> Result<Envelope<T>> {
+        self.send()?
+            .assert_content_type("application/json")?
+            .deserialize_data()
+    }
+
+    /// Sends the request and streams the response body into the **writer**,
+    /// the returned response has no body. If the writer fails the transfer is
+    /// aborted with [`RequestError::WriteFailed`], which is never retried.
+    pub fn send_to(mut self, writer: &mut dyn Write) -> Result<Response> {
+        let started_at = SystemTime::now();
+        let timer = Instant::now();
+        let response = self.perform(&mut |data| writer.write_all(data))?;
+
+        self.notify_observers(&response, started_at, timer.elapsed());
+        Ok(response)
+    }
+
+    /// Sends the request and calls **on_line** with every line of the response body
+    /// as soon as it arrives instead of buffering the whole body.
+    ///
+    /// Line endings (`\n` or `\r\n`) are stripped, the returned response has no body.
+    pub fn send_lines(mut self, mut on_line: impl FnMut(&str)) -> Result<Response> {
+        let started_at = SystemTime::now();
+        let timer = Instant::now();
+        let mut lines = LineBuffer::default();
+        let response = self.perform(&mut |data| {
+            lines.push(data, &mut on_line);
+            Ok(())
+        })?;
+        lines.finish(&mut on_line);
+
+        self.notify_observers(&response, started_at, timer.elapsed());
+        Ok(response)
+    }
+
+    /// Sends the request and calls **on_value** with the index of the pattern and the value
+    /// for every JSON value of the response body which matches one of the **patterns**,
+    /// as soon as it arrived, instead of buffering the whole body.
+    ///
+    /// Patterns are JSON pointers in which `*` stands for any key or array index, e.g.
+    /// `/data/*` for the items of the `data` array. **on_value** is called while the
+    /// transfer is paused, so a slow consumer holds back the download instead of
+    /// piling up data. The transfer is aborted as soon as the body turns out broken,
+    /// which is [`RequestError::InvalidJson`], or **on_value** returns an error, which
+    /// is returned as it is. Bodies of failed responses are not read, the returned
+    /// response has no body.
+    pub fn send_json_values(
+        mut self,
+        patterns: &[&str],
+        mut on_value: impl FnMut(usize, serde_json::Value) -> Result<()>,
+    ) -> Result<Response> {
+        let started_at = SystemTime::now();
+        let timer = Instant::now();
+        let mut stream = JsonStream::new(patterns);
+        let mut aborted = None;
+        let status = Rc::clone(&self.status);
+        let result = self.perform(&mut |data| {
+            // The body of a failed response is passed over, it is no JSON to read.
+            if (400..=600).contains(&status.get()) {
+                return Ok(());
+            }
+            stream.push(data, &mut on_value).map_err(|error| {
+                aborted = Some(error);
+                io::Error::other("aborted after an invalid value")
             })
-                .map_err(|_| RequestError::InvalidJson)?)
+        });
+        let response = match (result, aborted) {
+            (Err(RequestError::WriteFailed(_)), Some(error)) => return Err(error),
+            (result, _) => result?,
+        };
+        self.notify_observers(&response, started_at, timer.elapsed());
+
+        if response.ok() {
+            stream.finish(&mut on_value)?;
+        }
+        Ok(response)
+    }
+
+    /// Performs the transfer, passing the response body chunks to **write**.
+    ///
+    /// With the `tracing` feature every transfer is a `request` span, which records
+    /// the status or the error and the elapsed time when it ends.
+    #[cfg(feature = "tracing")]
+    fn perform(&mut self, write: &mut dyn FnMut(&[u8]) -> io::Result<()>) -> Result<Response> {
+        use tracing::field::{display, Empty};
+
+        let span = tracing::info_span!(
+            "request",
+            method = %self.method,
+            host = url::host(&self.url).unwrap_or(""),
+            path = url::path(&self.url),
+            trace_id = self.trace_id.as_deref(),
+            status = Empty,
+            error = Empty,
+            elapsed_ms = Empty,
+        );
+        let _entered = span.enter();
+        let timer = Instant::now();
+        let result = self.perform_transfer(write);
+        match result {
+            Ok(ref response) => span.record("status", response.status()),
+            Err(ref error) => span.record("error", display(error)),
+        };
+        span.record("elapsed_ms", timer.elapsed().as_millis() as u64);
+        result
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    fn perform(&mut self, write: &mut dyn FnMut(&[u8]) -> io::Result<()>) -> Result<Response> {
+        self.perform_transfer(write)
+    }
+
+    fn perform_transfer(
+        &mut self,
+        write: &mut dyn FnMut(&[u8]) -> io::Result<()>,
+    ) -> Result<Response> {
+        if self.client.offline {
+            return Err(RequestError::Offline(url::path(&self.url).to_string()));
+        }
+        let breaker = match self.client.circuit_breaker {
+            Some(ref breaker) => Rc::clone(breaker),
+            None => return self.timed_transfer(write),
+        };
+        if let Some(remaining) = breaker.remaining(self.client.clock.now()) {
+            return Err(RequestError::CircuitOpen(remaining));
+        }
+        let result = self.timed_transfer(write);
+        let recorded = match result {
+            Err(ref error) if error.is_transient() => {
+                breaker.record_failure(self.client.clock.now())
+            }
+            Err(_) => Ok(()),
+            Ok(_) => breaker.record_success(),
+        };
+        if let Err(error) = recorded {
+            self.client.warn(&Warning::CircuitBreakerWrite(error));
+        }
+        result
+    }
+
+    /// Transfer with the current adaptive timeout. An attempt which timed out took
+    /// at least the whole timeout, so it counts as that long for the next ones.
+    fn timed_transfer(
+        &mut self,
+        write: &mut dyn FnMut(&[u8]) -> io::Result<()>,
+    ) -> Result<Response> {
+        let adaptive = match self.client.adaptive_timeout {
+            Some(ref adaptive) => Rc::clone(adaptive),
+            None => return self.transfer(write),
+        };
+        let timeout = adaptive.current();
+        self.handle.timeout(timeout)?;
+        let result = self.transfer(write);
+        if let Err(RequestError::TimedOut(_)) = result {
+            adaptive.record(timeout);
+        }
+        result
+    }
+
+    fn transfer(&mut self, write: &mut dyn FnMut(&[u8]) -> io::Result<()>) -> Result<Response> {
+        let mut headers = curl::easy::List::new();
+        for (key, value) in &self.headers {
+            headers.append(&format!("{}: {}", key, value))?;
+        }
+        self.handle.http_headers(headers)?;
+        self.handle.url(&self.url)?;
+        if self.reader.is_some() || self.body.is_some() {
+            self.handle.upload(true)?;
+            if self.method == Method::Get {
+                // An upload is a PUT to curl unless the method is given explicitly.
+                self.handle.custom_request("GET")?;
+            }
+        }
+
+        let result = if let Some(mut reader) = self.reader.take() {
+            handle_request(
+                &mut self.handle,
+                self.client.max_response_size,
+                &self.status,
+                &mut |buffer| reader.read(buffer).map_err(|_| ReadError::Abort),
+                write,
+            )
+        } else {
+            match self.body {
+                Some(ref body) => {
+                    let mut body: &[u8] = &body[..];
+                    self.handle.in_filesize(body.len() as u64)?;
+                    handle_request(
+                        &mut self.handle,
+                        self.client.max_response_size,
+                        &self.status,
+                        &mut |buffer| Ok(body.read(buffer).unwrap_or(0)),
+                        write,
+                    )
+                }
+                None => handle_request(
+                    &mut self.handle,
+                    self.client.max_response_size,
+                    &self.status,
+                    &mut |_| Ok(0),
+                    write,
+                ),
+            }
+        };
+        self.client
+            .last_transfer_bytes
+            .set(bytes_transferred(&self.handle));
+        let mut response = result?;
+
+        if self.client.canonical_header_names {
+            for line in response.headers.iter_mut() {
+                if let Some((name, value)) = line.split_once(':') {
+                    *line = format!("{}:{}", headers::canonical_name(name), value);
+                }
+            }
+        }
+        response.sent_request_id = self.trace_id.clone();
+        Ok(response)
+    }
+
+    fn notify_observers(&self, response: &Response, started_at: SystemTime, elapsed: Duration) {
+        let exchange = Exchange {
+            method: &self.method,
+            url: &self.url,
+            request_headers: &self.headers,
+            request_body: self.body.as_deref(),
+            response,
+            started_at,
+            elapsed,
+        };
+        for observer in &self.client.observers {
+            observer.on_response(&exchange);
+        }
+        if let Some(ref adaptive) = self.client.adaptive_timeout {
+            adaptive.record(elapsed);
+        }
+        self.client.check_clock_skew(response);
+    }
+}
+
+/// A request which is ready to be sent, see [`Request::build`].
+///
+/// Unlike a [`Request`] it doesn't hold on to the curl handle of the client,
+/// so it can be kept, cloned and compared. Its `Debug` output has no query values,
+/// credential headers or secret body fields.
+#[derive(Clone, PartialEq)]
+pub struct PreparedRequest {
+    method: Method,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Option<Vec<u8>>,
+    idempotent: bool,
+    retry: bool,
+    follow_redirects: bool,
+    trace_id: Option<String>,
+}
+
+impl fmt::Debug for PreparedRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let headers: Vec<(&str, &str)> = self
+            .headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), redact::header(name, value)))
+            .collect();
+        f.debug_struct("PreparedRequest")
+            .field("method", &self.method)
+            .field("url", &redact::url(&self.url))
+            .field("headers", &headers)
+            .field("body", &self.body.as_deref().map(redact::body_excerpt))
+            .field("idempotent", &self.idempotent)
+            .field("retry", &self.retry)
+            .field("follow_redirects", &self.follow_redirects)
+            .field("trace_id", &self.trace_id)
+            .finish()
+    }
+}
+
+impl PreparedRequest {
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// Returns the absolute url with the query.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Returns the headers in the order they are sent.
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// Returns the value of the first header **name**, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn body(&self) -> Option<&[u8]> {
+        self.body.as_deref()
+    }
+}
+
+/// Whether the **verb** is a valid HTTP method token, which keeps it from
+/// smuggling anything else into the request line.
+fn is_token(verb: &str) -> bool {
+    !verb.is_empty()
+        && verb
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c))
+}
+
+type RetryPredicate = Rc<dyn Fn(&RetryContext) -> bool>;
+
+/// An attempt of a request, see [`Client::set_retry_if`].
+pub struct RetryContext<'a> {
+    pub method: &'a Method,
+    /// The attempt which failed, `0` for the first one.
+    pub attempt: u32,
+    /// The response, with its body, if the server answered.
+    pub response: Option<&'a Response>,
+    /// The error if the transfer failed.
+    pub error: Option<&'a RequestError>,
+}
+
+impl RetryContext<'_> {
+    /// Returns the status of the response, if the server answered.
+    pub fn status(&self) -> Option<HttpStatus> {
+        self.response.map(Response::status)
+    }
+
+    /// The default rules: retry after a connection failure, a timeout or a gateway error.
+    pub fn is_retryable(&self) -> bool {
+        self.status().is_some_and(is_retryable_status)
+            || self.error.is_some_and(RequestError::is_transient)
+    }
+}
+
+/// Gateway errors which usually go away when trying again.
+fn is_retryable_status(status: HttpStatus) -> bool {
+    matches!(status, 502..=504)
+}
+
+/// Performs the transfer on the **handle**, aborting it once the decoded body
+/// gets larger than **max_size**. The **status** is set as soon as it arrives.
+fn handle_request(
+    handle: &mut curl::easy::Easy,
+    max_size: Option<u64>,
+    status: &Cell<HttpStatus>,
+    read: &mut dyn FnMut(&mut [u8]) -> std::result::Result<usize, ReadError>,
+    write: &mut dyn FnMut(&[u8]) -> io::Result<()>,
+) -> Result<Response> {
+    let mut response_headers = vec![];
+    let mut content_length = 0;
+    let mut too_large = false;
+    let mut write_error = None;
+
+    {
+        let mut handle = handle.transfer();
+
+        handle.read_function(move |buffer| read(buffer))?;
+
+        handle.write_function(|data| {
+            content_length += data.len() as u64;
+            if max_size.is_some_and(|max_size| content_length > max_size) {
+                too_large = true;
+                return Ok(0);
+            }
+            match write(data) {
+                Ok(()) => Ok(data.len()),
+                Err(error) => {
+                    write_error = Some(error);
+                    Ok(0)
+                }
+            }
+        })?;
+
+        handle.header_function(|data| {
+            let line = String::from_utf8_lossy(data).into_owned();
+            // Every response of a redirect or a `100 Continue` starts with its status line.
+            if line.starts_with("HTTP/") {
+                if let Some(code) = line
+                    .split_whitespace()
+                    .nth(1)
+                    .and_then(|code| code.parse().ok())
+                {
+                    status.set(code);
+                }
+            }
+            response_headers.push(line);
+            true
+        })?;
+        let result = handle.perform();
+        drop(handle);
+        if too_large {
+            return Err(RequestError::ResponseTooLarge(max_size.unwrap_or_default()));
+        }
+        if let Some(error) = write_error {
+            return Err(RequestError::WriteFailed(error));
+        }
+        result?;
+    }
+
+    let (bytes_sent, bytes_received) = bytes_transferred(handle);
+    Ok(Response {
+        url: handle
+            .effective_url()?
+            .map(String::from)
+            .unwrap_or_default(),
+        status: handle.response_code()?,
+        headers: response_headers,
+        body: None,
+        transfer_length: handle.download_size()? as u64,
+        content_length,
+        bytes_sent,
+        bytes_received,
+        sent_request_id: None,
+    })
+}
+
+/// Returns the bytes sent and received by the latest transfer of the **handle**, as
+/// far as it got. The request size of curl includes the uploaded body, so adding the
+/// upload size would count it twice.
+fn bytes_transferred(handle: &curl::easy::Easy) -> (u64, u64) {
+    let sent = handle.request_size().unwrap_or(0);
+    let received = handle.header_size().unwrap_or(0) + handle.download_size().unwrap_or(0.0) as u64;
+    (sent, received)
+}
+
+/// Reassembles the lines of a body which arrives in chunks split at arbitrary bytes.
+#[derive(Default)]
+struct LineBuffer {
+    pending: Vec<u8>,
+}
+
+impl LineBuffer {
+    /// Append a chunk and emit every line it completes.
+    fn push(&mut self, data: &[u8], on_line: &mut dyn FnMut(&str)) {
+        self.pending.extend_from_slice(data);
+        let mut start = 0;
+        while let Some(position) = self.pending[start..].iter().position(|&b| b == b'\n') {
+            let end = start + position;
+            Self::emit(&self.pending[start..end], on_line);
+            start = end + 1;
+        }
+        self.pending.drain(..start);
+    }
+
+    /// Emit the last line if the body did not end with a line break.
+    fn finish(&mut self, on_line: &mut dyn FnMut(&str)) {
+        if !self.pending.is_empty() {
+            Self::emit(&self.pending, on_line);
+            self.pending.clear();
+        }
+    }
+
+    fn emit(line: &[u8], on_line: &mut dyn FnMut(&str)) {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        on_line(&String::from_utf8_lossy(line));
+    }
+}
+
+/// Type alias for **u32** http status.
+pub type HttpStatus = u32;
+
+/// The struct represents the HTTP response.
+#[derive(Clone, Debug)]
+pub struct Response {
+    url: String,
+    status: HttpStatus,
+    headers: Vec<String>,
+    body: Option<Vec<u8>>,
+    transfer_length: u64,
+    content_length: u64,
+    bytes_sent: u64,
+    bytes_received: u64,
+    sent_request_id: Option<String>,
+}
+
+impl Response {
+    /// Returns the url the response came from, which differs from the requested
+    /// one after following redirects.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Returns the path of the [`Response::url`].
+    pub fn path(&self) -> &str {
+        url::path(&self.url)
+    }
+
+    /// Returns the percent-decoded value of the query parameter **name** of the
+    /// [`Response::url`], e.g. a pagination cursor.
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        url::query_param(&self.url, name)
+    }
+
+    pub fn status(&self) -> HttpStatus {
+        self.status
+    }
+
+    /// Returns the raw response header lines.
+    pub fn headers(&self) -> &[String] {
+        &self.headers
+    }
+
+    /// Returns the status line of the final response, e.g. `HTTP/1.1 200 OK`.
+    pub fn status_line(&self) -> Option<&str> {
+        self.headers
+            .iter()
+            .rev()
+            .find(|line| line.starts_with("HTTP/"))
+            .map(|line| line.trim_end())
+    }
+
+    /// Returns the name and value pairs of the final response headers.
+    ///
+    /// Headers of intermediate responses (redirects, `100 Continue`) are skipped.
+    pub fn header_pairs(&self) -> impl Iterator<Item = (&str, &str)> {
+        let start = self
+            .headers
+            .iter()
+            .rposition(|line| line.starts_with("HTTP/"))
+            .map_or(0, |index| index + 1);
+        self.headers[start..].iter().filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            Some((name.trim(), value.trim()))
+        })
+    }
+
+    /// Returns the value of the response header **name**, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.header_pairs()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// Check that the `Content-Type` header is **expected**, ignoring case and
+    /// parameters like `charset`.
+    pub fn assert_content_type(&self, expected: &str) -> Result<&Response> {
+        let received = self.header("Content-Type");
+        let media_type = received.and_then(|value| value.split(';').next());
+        match media_type {
+            Some(media_type) if media_type.trim().eq_ignore_ascii_case(expected) => Ok(self),
+            _ => Err(RequestError::UnexpectedContentType {
+                expected: expected.to_string(),
+                received: received.map(String::from),
+            }),
+        }
+    }
+
+    /// Returns the request id to quote when contacting the support: the one the server
+    /// returned in `X-Request-Id` or `Request-Id`, or else the one sent with
+    /// [`Request::with_trace_id`].
+    pub fn request_id(&self) -> Option<&str> {
+        self.header("X-Request-Id")
+            .or_else(|| self.header("Request-Id"))
+            .or(self.sent_request_id.as_deref())
+    }
+
+    /// Returns the `Preference-Applied` header, which tells what the server made of
+    /// [`Request::prefer`], e.g. `return=minimal`.
+    pub fn preference_applied(&self) -> Option<&str> {
+        self.header("Preference-Applied")
+    }
+
+    /// Returns the target url of the `Link` header entry with the relation type **rel**,
+    /// e.g. `next` for the next page. The url may be relative to the request url.
+    pub fn link(&self, rel: &str) -> Option<&str> {
+        self.header_pairs()
+            .filter(|(key, _)| key.eq_ignore_ascii_case("Link"))
+            .find_map(|(_, value)| url::find_link(value, rel))
+    }
+
+    /// Returns the file name suggested by the `Content-Disposition` header.
+    ///
+    /// Directories are stripped from the name, so it can not be used for path traversal.
+    pub fn suggested_filename(&self) -> Option<String> {
+        self.header("Content-Disposition")
+            .and_then(headers::disposition_filename)
+    }
+
+    /// Returns the time reported by the server in the `Date` header.
+    pub fn server_date(&self) -> Option<SystemTime> {
+        self.header("Date").and_then(date::parse_http_date)
+    }
+
+    /// Returns the response body bytes.
+    pub fn body(&self) -> &[u8] {
+        self.body.as_deref().unwrap_or(&[])
+    }
+
+    /// Consumes the response and moves the body out without copying it.
+    pub fn into_body(self) -> Option<Vec<u8>> {
+        self.body
+    }
+
+    /// Consumes the response into its status, raw header lines and body.
+    pub fn into_parts(self) -> (HttpStatus, Vec<String>, Option<Vec<u8>>) {
+        (self.status, self.headers, self.body)
+    }
+
+    pub fn failed(&self) -> bool {
+        self.status >= 400 && self.status <= 600
+    }
+
+    pub fn ok(&self) -> bool {
+        !self.failed()
+    }
+
+    /// Returns the size of the body as it was transferred, which is smaller than
+    /// the [`Response::content_length`] if the server compressed it.
+    pub fn transfer_length(&self) -> u64 {
+        self.transfer_length
+    }
+
+    /// Returns the size of the decoded body, also if it was streamed instead of
+    /// kept in the response. Unlike the `Content-Length` header this is never the
+    /// compressed size.
+    pub fn content_length(&self) -> u64 {
+        self.content_length
+    }
+
+    /// Returns how many bytes of request headers and body went over the wire,
+    /// with the framing of a chunked body, `0` for a response from the
+    /// [`Client::set_http_cache`].
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent
+    }
+
+    /// Returns how many bytes of response headers and body came over the wire,
+    /// with the headers of redirects and the body as transferred, `0` for a response
+    /// from the [`Client::set_http_cache`].
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received
+    }
+
+    /// Whether the body is missing or has only whitespace, like the one of `204 No Content`.
+    pub fn is_empty_body(&self) -> bool {
+        self.body().iter().all(u8::is_ascii_whitespace)
+    }
+
+    /// Deserialize the response body into the given type
+    ///
+    /// An empty body is read as JSON `null`, so it deserializes into `()` or `None`.
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T> {
+        if self.ok() {
+            let body = if self.is_empty_body() {
+                &b"null"[..]
+            } else {
+                self.body()
+            };
+            serde_json::from_slice(body).map_err(|_| RequestError::InvalidJson)
+        } else {
+            Err(RequestError::RequestFailed)
+        }
+    }
+
+    /// Deserialize the `data` field of an enveloped response body like
+    /// `{"data": [...], "meta": {"total": 3}}`, use [`Response::deserialize`]
+    /// for a bare payload.
+    pub fn deserialize_data<T: DeserializeOwned>(&self) -> Result<Envelope<T>> {
+        if self.ok() {
+            envelope::parse(self.body())
         } else {
             Err(RequestError::RequestFailed)
         }
@@ -244,11 +1806,1121 @@ impl Response {
 #[derive(Error, Debug)]
 pub enum RequestError {
     #[error(transparent)]
-    CurlError(#[from] curl::Error),
+    CurlError(curl::Error),
+    #[error("Couldn't resolve host{}", curl_detail(.0))]
+    CouldntResolveHost(#[source] curl::Error),
+    #[error("Couldn't connect to the server{}", curl_detail(.0))]
+    CouldntConnect(#[source] curl::Error),
+    #[error("Connection timed out{}", curl_detail(.0))]
+    TimedOut(#[source] curl::Error),
+    #[error("TLS certificate invalid{}", curl_detail(.0))]
+    InvalidCertificate(#[source] curl::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
     #[error("Request failed")]
     RequestFailed,
     #[error("Could not serialize value as JSON")]
     InvalidJsonBody,
     #[error("Could not parse JSON response")]
     InvalidJson,
+    #[error("Response body is not valid UTF-8")]
+    InvalidText,
+    #[error("DNS-over-HTTPS url must use https: {0}")]
+    InsecureDohUrl(String),
+    #[error(
+        "Refusing to send a request to {0} without https, credentials would be sent in cleartext."
+    )]
+    InsecureUrl(String),
+    #[error(
+        "Request rejected with status {status}{}{}{}",
+        quote_request_id(request_id),
+        quote_body("response", response_body),
+        quote_body("request body", request_body)
+    )]
+    Rejected {
+        status: HttpStatus,
+        request_id: Option<String>,
+        response_body: Option<String>,
+        request_body: Option<String>,
+    },
+    #[error(
+        "Expected a response of type {expected}, but got {}",
+        received.as_deref().unwrap_or("one without Content-Type")
+    )]
+    UnexpectedContentType {
+        expected: String,
+        received: Option<String>,
+    },
+    #[error(
+        "The server seems to be down, no requests are sent for another {}s",
+        .0.as_secs() + u64::from(.0.subsec_nanos() > 0)
+    )]
+    CircuitOpen(Duration),
+    #[error("Can't request {0} in offline mode")]
+    Offline(String),
+    #[error("Could not write the response body: {0}")]
+    WriteFailed(#[source] std::io::Error),
+    #[error("Response body is larger than {0} bytes")]
+    ResponseTooLarge(u64),
+    #[error("A request with a streamed body can't be prepared")]
+    StreamedBody,
+    #[error("Invalid HTTP method: {0}")]
+    InvalidMethod(String),
+    #[error("Unknown or unsupported curl option: {0}")]
+    UnknownCurlOption(String),
+    #[error("Invalid value {1} for curl option {0}")]
+    InvalidCurlOption(String, i64),
+}
+
+impl From<curl::Error> for RequestError {
+    fn from(error: curl::Error) -> RequestError {
+        if error.is_couldnt_resolve_host() || error.is_couldnt_resolve_proxy() {
+            RequestError::CouldntResolveHost(error)
+        } else if error.is_couldnt_connect() {
+            RequestError::CouldntConnect(error)
+        } else if error.is_operation_timedout() {
+            RequestError::TimedOut(error)
+        } else if error.is_peer_failed_verification()
+            || error.is_ssl_cacert()
+            || error.is_ssl_cacert_badfile()
+            || error.is_ssl_certproblem()
+        {
+            RequestError::InvalidCertificate(error)
+        } else {
+            RequestError::CurlError(error)
+        }
+    }
+}
+
+fn curl_detail(error: &curl::Error) -> String {
+    error
+        .extra_description()
+        .map(|detail| format!(": {}", detail))
+        .unwrap_or_default()
+}
+
+fn quote_request_id(id: &Option<String>) -> String {
+    id.as_ref()
+        .map(|id| format!(", request id {}", id))
+        .unwrap_or_default()
+}
+
+fn quote_body(label: &str, body: &Option<String>) -> String {
+    body.as_ref()
+        .map(|body| format!(", {}: {}", label, body))
+        .unwrap_or_default()
+}
+
+impl RequestError {
+    /// Whether the error is likely to go away when trying again.
+    fn is_transient(&self) -> bool {
+        match self {
+            RequestError::CouldntResolveHost(_)
+            | RequestError::CouldntConnect(_)
+            | RequestError::TimedOut(_) => true,
+            RequestError::CurlError(error) => {
+                error.is_send_error() || error.is_recv_error() || error.is_got_nothing()
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+
+    /// Serve the raw HTTP **responses** to one connection each, returns the base url.
+    fn serve<R: AsRef<[u8]> + Send + 'static>(responses: Vec<R>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                stream.write_all(response.as_ref()).unwrap();
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    /// Answer one request with an empty response, the handle returns its header lines.
+    fn serve_capture_headers() -> (u16, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+            let mut headers = vec![];
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                headers.push(line.trim_end().to_string());
+                line.clear();
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            headers
+        });
+        (port, server)
+    }
+
+    /// Answer a connection each after the **delays**, all of them at the same time.
+    fn serve_delayed(delays: Vec<Duration>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for delay in delays {
+                let (mut stream, _) = listener.accept().unwrap();
+                thread::spawn(move || {
+                    let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+                    let mut line = String::new();
+                    while reader.read_line(&mut line).unwrap_or(0) > 2 {
+                        line.clear();
+                    }
+                    thread::sleep(delay);
+                    // The client may have given up already.
+                    let _ = stream.write_all(
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    );
+                });
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    /// Answer one request with its method and body, and the size of the request
+    /// in `X-Request-Bytes`.
+    fn serve_echo() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut length = 0;
+            let mut chunked = false;
+            let mut request_bytes = request_line.len();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                request_bytes += line.len();
+                let lower = line.to_ascii_lowercase();
+                if let Some(value) = lower.strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                chunked |= lower.starts_with("transfer-encoding: chunked");
+                if lower.starts_with("expect: 100-continue") {
+                    stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").unwrap();
+                }
+                line.clear();
+            }
+            // The empty line after the headers.
+            request_bytes += 2 + length;
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            while chunked {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+                let size = usize::from_str_radix(line.trim(), 16).unwrap();
+                let mut chunk = vec![0; size + 2];
+                reader.read_exact(&mut chunk).unwrap();
+                body.extend_from_slice(&chunk[..size]);
+                request_bytes += line.len() + chunk.len();
+                chunked = size > 0;
+            }
+
+            let method = request_line.split(' ').next().unwrap();
+            let echo = format!("{} {}", method, String::from_utf8_lossy(&body));
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nX-Request-Bytes: {}\r\nConnection: close\r\n\r\n{}",
+                echo.len(),
+                request_bytes,
+                echo
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+        format!("http://{}", address)
+    }
+
+    #[test]
+    fn test_get_with_body() {
+        let client = Client::new(&serve_echo());
+        let response = client
+            .request(Method::Get, "/search")
+            .and_then(|request| request.with_body(&br#"{"query":"rust"}"#[..]))
+            .and_then(Request::send)
+            .unwrap();
+        assert_eq!(response.body(), br#"GET {"query":"rust"}"#);
+        let request_bytes = response.header("X-Request-Bytes").unwrap().parse::<u64>();
+        assert_eq!(response.bytes_sent(), request_bytes.unwrap());
+        assert_eq!(client.last_transfer_bytes().0, response.bytes_sent());
+
+        let client = Client::new(&serve_echo());
+        let response = client
+            .request(Method::Get, "/search")
+            .and_then(|request| request.with_reader(&b"streamed"[..]))
+            .and_then(Request::send)
+            .unwrap();
+        assert_eq!(response.body(), b"GET streamed");
+        let request_bytes = response.header("X-Request-Bytes").unwrap().parse::<u64>();
+        assert_eq!(response.bytes_sent(), request_bytes.unwrap());
+    }
+
+    #[test]
+    fn test_pages_end_after_repeated_errors() {
+        let first = "HTTP/1.1 200 OK\r\nLink: </2>; rel=\"next\"\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        // The server is gone after the first page, so the second one keeps failing.
+        let client = Client::new(&serve(vec![first]));
+        let mut pages = client.pages("/1").stop_on_error(false).max_errors(2);
+        assert_eq!(pages.next().unwrap().unwrap().status(), 200);
+        assert!(pages.next().unwrap().is_err());
+        assert!(pages.next().unwrap().is_err());
+        assert!(pages.next().is_none());
+    }
+
+    #[test]
+    fn test_idempotent_requests_are_retried() {
+        let unavailable =
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let ok = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok";
+
+        let clock = Rc::new(testing::MockClock::new(SystemTime::now()));
+        let mut client = Client::new(&serve(vec![unavailable, unavailable, ok]));
+        client.set_clock(clock.clone());
+        client.set_retries(2);
+        let response = client.get("/").unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body(), b"ok");
+        assert_eq!(clock.sleeps(), vec![RETRY_DELAY, RETRY_DELAY * 2]);
+
+        let client = Client::new(&serve(vec![unavailable]));
+        assert_eq!(client.get("/").unwrap().status(), 503);
+    }
+
+    #[test]
+    fn test_retry_if() {
+        let busy =
+            "HTTP/1.1 200 OK\r\nContent-Length: 14\r\nConnection: close\r\n\r\n{\"code\": 1503}";
+        let ok =
+            "HTTP/1.1 200 OK\r\nContent-Length: 13\r\nConnection: close\r\n\r\n{\"code\": 200}";
+        let unavailable =
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+
+        let attempts = Rc::new(Cell::new(0));
+        let mut client = Client::new(&serve(vec![busy, ok, unavailable]));
+        client.set_clock(Rc::new(testing::MockClock::new(SystemTime::now())));
+        client.set_retries(3);
+        let seen = attempts.clone();
+        client.set_retry_if(move |context| {
+            seen.set(context.attempt + 1);
+            context.is_retryable()
+                || context
+                    .response
+                    .is_some_and(|response| response.body().ends_with(b"1503}"))
+        });
+        assert_eq!(client.get("/").unwrap().body(), br#"{"code": 200}"#);
+        // Asked about the busy and the ok response.
+        assert_eq!(attempts.get(), 2);
+
+        client.set_retry_if(|context| context.status() != Some(503));
+        assert_eq!(client.get("/").unwrap().status(), 503);
+    }
+
+    #[test]
+    fn test_adaptive_timeout_grows_after_timeouts() {
+        // The server never answers in time, so every request times out.
+        let never = Duration::from_secs(60);
+        let mut client = Client::new(&serve_delayed(vec![never, never]));
+        client.set_adaptive_timeout(Duration::from_millis(20), Duration::from_secs(5));
+        let adaptive = Rc::clone(client.adaptive_timeout.as_ref().unwrap());
+        for _ in 0..3 {
+            adaptive.record(Duration::from_millis(1));
+        }
+        assert_eq!(adaptive.current(), Duration::from_millis(20));
+
+        // A timed out request counts as taking the whole timeout.
+        assert!(matches!(client.get("/"), Err(RequestError::TimedOut(_))));
+        assert_eq!(adaptive.current(), Duration::from_millis(80));
+        assert!(matches!(client.get("/"), Err(RequestError::TimedOut(_))));
+        assert_eq!(adaptive.current(), Duration::from_millis(320));
+    }
+
+    #[test]
+    fn test_get_text_checks_status() {
+        let ok = "HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello";
+        let missing =
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 4\r\nConnection: close\r\n\r\nnope";
+
+        let client = Client::new(&serve(vec![ok, missing]));
+        assert_eq!(client.get_text("/").unwrap(), "hello");
+        assert!(matches!(
+            client.get_bytes("/"),
+            Err(RequestError::RequestFailed)
+        ));
+    }
+
+    #[test]
+    fn test_send_json_values() {
+        let ok = "HTTP/1.1 200 OK\r\nContent-Length: 37\r\nConnection: close\r\n\r\n\
+                  {\"data\": [{\"key\": \"g\"}, \"m\"], \"n\": 1}";
+        let broken =
+            "HTTP/1.1 200 OK\r\nContent-Length: 10\r\nConnection: close\r\n\r\n{\"data\": [";
+        let failed = "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 5\r\n\
+                      Connection: close\r\n\r\noops!";
+        let client = Client::new(&serve(vec![ok, broken, failed]));
+
+        let mut values = vec![];
+        client
+            .request(Method::Get, "/")
+            .unwrap()
+            .send_json_values(&["/data/*"], |_, value| {
+                values.push(value);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(
+            values,
+            vec![serde_json::json!({"key": "g"}), serde_json::json!("m")]
+        );
+
+        let result = client
+            .request(Method::Get, "/")
+            .unwrap()
+            .send_json_values(&["/data/*"], |_, _| Ok(()));
+        assert!(matches!(result, Err(RequestError::InvalidJson)));
+        let response = client
+            .request(Method::Get, "/")
+            .unwrap()
+            .send_json_values(&["/data/*"], |_, _| Ok(()))
+            .unwrap();
+        assert_eq!(response.status(), 500);
+    }
+
+    #[test]
+    fn test_send_json_values_aborts() {
+        // Less body than announced, a transfer which is not aborted fails as cut off.
+        let broken = "HTTP/1.1 200 OK\r\nContent-Length: 1000\r\nConnection: close\r\n\r\n\
+                      {\"data\": [1, oops, 2";
+        let values = "HTTP/1.1 200 OK\r\nContent-Length: 1000\r\nConnection: close\r\n\r\n\
+                      {\"data\": [1, 2, 3";
+        let client = Client::new(&serve(vec![broken, values]));
+
+        let result = client
+            .request(Method::Get, "/")
+            .unwrap()
+            .send_json_values(&["/data/*"], |_, _| Ok(()));
+        assert!(matches!(result, Err(RequestError::InvalidJson)));
+
+        let mut calls = 0;
+        let result =
+            client
+                .request(Method::Get, "/")
+                .unwrap()
+                .send_json_values(&["/data/*"], |_, _| {
+                    calls += 1;
+                    Err(RequestError::InvalidText)
+                });
+        assert!(matches!(result, Err(RequestError::InvalidText)));
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_buffer_size_keeps_large_bodies_intact() {
+        let body: Vec<u8> = (0..4_000_000u32).map(|i| (i % 251) as u8).collect();
+        let mut response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        response.extend_from_slice(&body);
+
+        let mut client = Client::new(&serve(vec![response]));
+        client.set_buffer_size(1024 * 1024);
+        assert!(client.get_bytes("/export").unwrap() == body);
+    }
+
+    #[test]
+    fn test_http_cache_revalidates_with_etag() {
+        let ok = "HTTP/1.1 200 OK\r\nCache-Control: no-cache\r\nETag: \"v1\"\r\nContent-Length: 6\r\nConnection: close\r\n\r\ncached";
+        let not_modified =
+            "HTTP/1.1 304 Not Modified\r\nCache-Control: max-age=60\r\nConnection: close\r\n\r\n";
+        let dir = std::env::temp_dir().join(format!("curl-http-revalidate-{}", process::id()));
+
+        let clock = Rc::new(testing::MockClock::new(SystemTime::now()));
+        let mut client = Client::new(&serve(vec![ok, not_modified]));
+        client.set_http_cache(&dir);
+        client.set_clock(clock.clone());
+        assert_eq!(client.get_text("/").unwrap(), "cached");
+        assert_eq!(client.get_text("/").unwrap(), "cached");
+        // Fresh for a minute now, the server would refuse a third connection.
+        clock.advance(Duration::from_secs(59));
+        assert_eq!(client.get_text("/").unwrap(), "cached");
+        clock.advance(Duration::from_secs(2));
+        assert!(client.get_text("/").is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_http_cache_is_best_effort() {
+        let ok = "HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok";
+        // A file where the cache directory should be created.
+        let file = std::env::temp_dir().join(format!("curl-http-no-cache-{}", process::id()));
+        fs::write(&file, "").unwrap();
+
+        struct Warnings(RefCell<Vec<String>>);
+        impl Observer for Warnings {
+            fn on_warning(&self, warning: &Warning) {
+                self.0.borrow_mut().push(warning.to_string());
+            }
+        }
+        let warnings = Rc::new(Warnings(RefCell::new(vec![])));
+
+        let mut client = Client::new(&serve(vec![ok, ok]));
+        client.set_http_cache(file.join("cache"));
+        client.add_observer(warnings.clone());
+        assert_eq!(client.get_text("/").unwrap(), "ok");
+        assert_eq!(client.get_text("/").unwrap(), "ok");
+        let warnings = warnings.0.borrow();
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].starts_with("Could not write the http cache: "));
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_send_checked_quotes_rejected_body() {
+        let bad_request =
+            "HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let client = Client::new(&serve(vec![bad_request]));
+        let error = client
+            .request(Method::Post, "/shortcuts")
+            .and_then(|request| {
+                request.with_json_body(&serde_json::json!({"key": "g", "token": "t"}))
+            })
+            .and_then(Request::send_checked)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"Request rejected with status 400, request body: {"key":"g","token":"[REDACTED]"}"#
+        );
+
+        let conflict = "HTTP/1.1 409 Conflict\r\nContent-Length: 44\r\nConnection: close\r\n\r\n\
+                        {\"message\": \"Key exists\", \"token\": \"secret\"}";
+        let client = Client::new(&serve(vec![conflict]));
+        let error = client
+            .request(Method::Post, "/shortcuts")
+            .and_then(|request| request.with_body("g"))
+            .and_then(Request::send_checked)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"Request rejected with status 409, response: {"message":"Key exists","token":"[REDACTED]"}, request body: g"#
+        );
+    }
+
+    #[test]
+    fn test_exists() {
+        let response = |status: &str| {
+            format!(
+                "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            )
+        };
+        let client = Client::new(&serve(vec![
+            response("200 OK"),
+            response("404 Not Found"),
+            response("405 Method Not Allowed"),
+            response("200 OK"),
+            response("500 Internal Server Error"),
+        ]));
+        assert!(client.exists("/shortcuts/a").unwrap());
+        assert!(!client.exists("/shortcuts/b").unwrap());
+        assert!(client.exists("/shortcuts/c").unwrap());
+        assert!(matches!(
+            client.exists("/shortcuts/d"),
+            Err(RequestError::RequestFailed)
+        ));
+    }
+
+    #[test]
+    fn test_clones_share_cookies() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let mut cookies = vec![];
+            for response in &["Set-Cookie: session=abc; Path=/\r\n", ""] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    if line.to_ascii_lowercase().starts_with("cookie:") {
+                        cookies.push(line.trim_end().to_string());
+                    }
+                    line.clear();
+                }
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\n{}Content-Length: 0\r\nConnection: close\r\n\r\n",
+                    response
+                )
+                .unwrap();
+            }
+            cookies
+        });
+
+        let mut client = Client::new(&format!("http://{}", address));
+        client.set_cookies(true);
+        let clone = client.clone();
+        client.get("/login").unwrap();
+        clone.get("/shortcuts").unwrap();
+        assert_eq!(server.join().unwrap(), vec!["Cookie: session=abc"]);
+    }
+
+    #[test]
+    fn test_offline_serves_only_the_cache() {
+        let dir = std::env::temp_dir().join(format!("curl-http-offline-{}", std::process::id()));
+        let cached = "HTTP/1.1 200 OK\r\nCache-Control: max-age=0\r\nETag: \"v1\"\r\n\
+                      Content-Length: 6\r\nConnection: close\r\n\r\ncached";
+        let mut client = Client::new(&serve(vec![cached]));
+        client.set_http_cache(&dir);
+        assert_eq!(client.get_text("/a").unwrap(), "cached");
+
+        client.set_offline(true);
+        assert_eq!(client.get_text("/a").unwrap(), "cached");
+        assert!(matches!(
+            client.get("/b?access_token=secret"),
+            Err(RequestError::Offline(ref path)) if path == "/b"
+        ));
+        assert!(matches!(
+            client.request(Method::Post, "/a").map(|_| ()),
+            Err(RequestError::Offline(_))
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_response_url_components() {
+        let redirect = "HTTP/1.1 302 Found\r\nLocation: /shortcuts?cursor=a%2Fb&page=2\r\n\
+                        Content-Length: 0\r\nConnection: close\r\n\r\n";
+        let ok = "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let base_url = serve(vec![redirect, ok]);
+        let mut client = Client::new(&base_url);
+        client.set_follow_redirects(true);
+
+        let response = client.get("/start?page=1").unwrap();
+        assert_eq!(
+            response.url(),
+            format!("{}/shortcuts?cursor=a%2Fb&page=2", base_url)
+        );
+        assert_eq!(response.path(), "/shortcuts");
+        assert_eq!(response.query_param("cursor").as_deref(), Some("a/b"));
+        assert_eq!(response.query_param("page").as_deref(), Some("2"));
+        assert_eq!(response.query_param("missing"), None);
+    }
+
+    #[test]
+    fn test_request_overrides_client_defaults() {
+        let redirect =
+            "HTTP/1.1 302 Found\r\nLocation: /b\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let ok = "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let unavailable =
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+
+        let mut client = Client::new(&serve(vec![redirect, ok, redirect, unavailable]));
+        client.set_follow_redirects(true);
+        client.set_retries(2);
+        let response = client.get("/a").unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.path(), "/b");
+        let response = client
+            .request(Method::Get, "/a")
+            .and_then(Request::no_redirect)
+            .and_then(Request::send)
+            .unwrap();
+        assert_eq!(response.status(), 302);
+        assert_eq!(response.path(), "/a");
+        // A retry would fail to connect, the server is gone after this response.
+        let response = client
+            .request(Method::Get, "/a")
+            .and_then(Request::no_retry)
+            .and_then(Request::send)
+            .unwrap();
+        assert_eq!(response.status(), 503);
+    }
+
+    #[test]
+    fn test_custom_verbs_are_not_retried_by_default() {
+        let unavailable =
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let ok = "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let purge = || Method::Custom("PURGE".to_string());
+
+        let clock = Rc::new(testing::MockClock::new(SystemTime::now()));
+        let mut client = Client::new(&serve(vec![unavailable, unavailable, ok]));
+        client.set_clock(clock.clone());
+        client.set_retries(1);
+        let response = client.request(purge(), "/").unwrap().send().unwrap();
+        assert_eq!(response.status(), 503);
+        assert!(clock.sleeps().is_empty());
+        // Succeeds only with a retry after the second 503.
+        let response = client
+            .request(purge(), "/")
+            .and_then(|request| request.idempotent(true))
+            .and_then(Request::send)
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(clock.sleeps(), vec![RETRY_DELAY]);
+
+        assert!(matches!(
+            client.request(Method::Custom("GET / HTTP/1.1\r\nX".to_string()), "/"),
+            Err(RequestError::InvalidMethod(_))
+        ));
+    }
+
+    #[test]
+    fn test_empty_body_deserializes_to_nothing() {
+        let no_content = "HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n";
+        let client = Client::new(&serve(vec![no_content]));
+        let response = client.delete("/shortcuts/1").unwrap();
+        assert!(response.is_empty_body());
+        assert!(response.deserialize::<()>().is_ok());
+        assert_eq!(response.deserialize::<Option<u32>>().unwrap(), None);
+        assert!(matches!(
+            response.deserialize::<u32>(),
+            Err(RequestError::InvalidJson)
+        ));
+    }
+
+    #[test]
+    fn test_compressed_lengths() {
+        let gzip: &[u8] = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\xff\x4b\x4c\x1c\x05\xa3\x60\x14\x0c\x77\x00\x00\x03\xda\x38\x9a\xe8\x03\x00\x00";
+        let mut response = b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: 29\r\nConnection: close\r\n\r\n".to_vec();
+        response.extend_from_slice(gzip);
+
+        let mut client = Client::new(&serve(vec![response.clone(), response]));
+        client.set_accept_compression(true);
+        let response = client.get("/").unwrap();
+        assert_eq!(response.body(), &[b'a'; 1000][..]);
+        assert_eq!(response.transfer_length(), 29);
+        assert_eq!(response.content_length(), 1000);
+        assert_eq!(response.bytes_received(), 111);
+
+        client.set_max_response_size(100);
+        assert!(matches!(
+            client.get("/"),
+            Err(RequestError::ResponseTooLarge(100))
+        ));
+    }
+
+    #[test]
+    fn test_doh_url_must_use_https() {
+        let mut client = Client::new("https://example.com");
+        let error = client
+            .set_doh_url("http://dns.example.com/dns-query")
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "DNS-over-HTTPS url must use https: http://dns.example.com/dns-query"
+        );
+        assert!(client.set_doh_url("ftp://dns.example.com").is_err());
+        assert!(client.doh_url.is_none());
+
+        client
+            .set_doh_url("HTTPS://dns.example.com/dns-query")
+            .unwrap();
+        assert_eq!(
+            client.doh_url.as_deref(),
+            Some("HTTPS://dns.example.com/dns-query")
+        );
+    }
+
+    #[test]
+    fn test_download() {
+        let ok = "HTTP/1.1 200 OK\r\nContent-Disposition: attachment; filename=\"shortcuts.json\"\r\nContent-Length: 2\r\nConnection: close\r\n\r\n[]";
+        let not_found = "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let dir = std::env::temp_dir().join(format!("curl-http-download-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let files = || fs::read_dir(&dir).unwrap().count();
+
+        let mut client = Client::new(&serve(vec![ok, not_found]));
+        let path = client.download("/export", &dir).unwrap();
+        assert_eq!(path, dir.join("shortcuts.json"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[]");
+
+        // Neither a rejected nor an unsent request leaves a partial file behind.
+        let target = dir.join("missing.json");
+        assert!(matches!(
+            client.download("/missing", &target),
+            Err(RequestError::RequestFailed)
+        ));
+        assert_eq!(files(), 1);
+        client.set_offline(true);
+        assert!(matches!(
+            client.download("/export", &target),
+            Err(RequestError::Offline(_))
+        ));
+        assert_eq!(files(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_failed() {
+        struct ClosedPipe;
+        impl Write for ClosedPipe {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let response = "HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello";
+        let mut client = Client::new(&serve(vec![response, response]));
+        client.set_retries(1);
+        let error = client
+            .request(Method::Get, "/")
+            .unwrap()
+            .send_to(&mut ClosedPipe)
+            .unwrap_err();
+        match &error {
+            RequestError::WriteFailed(error) => {
+                assert_eq!(error.kind(), io::ErrorKind::BrokenPipe)
+            }
+            error => panic!("unexpected error {:?}", error),
+        }
+        assert!(!error.is_transient());
+        assert_eq!(
+            error.to_string(),
+            "Could not write the response body: closed"
+        );
+        // The request and the response headers went over the wire before the write failed.
+        let (sent, received) = client.last_transfer_bytes();
+        assert!(sent > 0);
+        assert!(received >= (response.len() - 5) as u64);
+
+        // The failed write aborted only that transfer.
+        assert_eq!(client.get("/").unwrap().body(), b"hello");
+    }
+
+    #[test]
+    fn test_method_classification() {
+        assert_eq!("delete".parse::<Method>().unwrap(), Method::Delete);
+        assert_eq!(
+            "PURGE".parse::<Method>().unwrap(),
+            Method::Custom("PURGE".to_string())
+        );
+        assert!("GET /".parse::<Method>().is_err());
+
+        let safe = [Method::Get, Method::Head];
+        let idempotent = [Method::Get, Method::Head, Method::Put, Method::Delete];
+        let others = [
+            Method::Post,
+            Method::Patch,
+            Method::Custom("PURGE".to_string()),
+        ];
+
+        assert!(safe.iter().all(Method::is_safe));
+        assert!(idempotent.iter().all(Method::is_idempotent));
+        assert!(!Method::Put.is_safe() && !Method::Delete.is_safe());
+        assert!(others
+            .iter()
+            .all(|method| !method.is_safe() && !method.is_idempotent()));
+    }
+
+    #[test]
+    fn test_single_valued_headers_are_replaced() {
+        let mut client = Client::new("https://example.com");
+        client.set_referer("https://example.com/a");
+        let request = client
+            .request(Method::Get, "/")
+            .and_then(|request| request.with_header("user-agent", "custom"))
+            .and_then(|request| request.with_referer("https://example.com/b"))
+            .and_then(|request| request.with_header("Accept", "text/plain"))
+            .and_then(|request| request.with_header("Accept", "application/json"))
+            .unwrap();
+
+        let headers: Vec<(&str, &str)> = request
+            .headers
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            headers,
+            vec![
+                ("user-agent", "custom"),
+                ("Referer", "https://example.com/b"),
+                ("Accept", "text/plain"),
+                ("Accept", "application/json"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_curl_errors_are_categorized() {
+        let error = |code| RequestError::from(curl::Error::new(code));
+        assert!(matches!(error(6), RequestError::CouldntResolveHost(_)));
+        assert!(matches!(error(7), RequestError::CouldntConnect(_)));
+        assert!(matches!(error(28), RequestError::TimedOut(_)));
+        assert!(matches!(error(60), RequestError::InvalidCertificate(_)));
+        assert!(matches!(error(52), RequestError::CurlError(_)));
+        assert_eq!(error(28).to_string(), "Connection timed out");
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let error = Client::new(&base_url).get("/").unwrap_err();
+        assert!(matches!(error, RequestError::CouldntConnect(_)));
+        assert!(error
+            .to_string()
+            .starts_with("Couldn't connect to the server: "));
+    }
+
+    #[test]
+    fn test_assert_content_type() {
+        let json = "HTTP/1.1 200 OK\r\nContent-Type: Application/JSON; charset=utf-8\r\n\
+                    Content-Length: 11\r\nConnection: close\r\n\r\n{\"data\": 1}";
+        let html = "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\
+                    Content-Length: 6\r\nConnection: close\r\n\r\n<html>";
+        let client = Client::new(&serve(vec![json, html]));
+
+        let envelope: Envelope<u32> = client
+            .request(Method::Get, "/")
+            .and_then(Request::send_json_data)
+            .unwrap();
+        assert_eq!(envelope.data, 1);
+        let error = client
+            .request(Method::Get, "/")
+            .and_then(Request::send_json_data::<u32>)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Expected a response of type application/json, but got text/html"
+        );
+    }
+
+    #[test]
+    fn test_host_overrides() {
+        let (port, server) = serve_capture_headers();
+        let client = Client::new(&format!("http://127.0.0.1:{}", port));
+        client
+            .request(Method::Get, "/")
+            .and_then(|request| request.with_host("shortcuts.example"))
+            .and_then(Request::send)
+            .unwrap();
+        let headers = server.join().unwrap();
+        assert!(headers.contains(&"Host: shortcuts.example".to_string()));
+
+        let (port, server) = serve_capture_headers();
+        let mut client = Client::new(&format!("http://staging.example:{}", port));
+        client.add_resolve_override("staging.example", port, "127.0.0.1");
+        assert_eq!(client.get("/").unwrap().status(), 200);
+        let headers = server.join().unwrap();
+        assert!(headers.contains(&format!("Host: staging.example:{}", port)));
+    }
+
+    #[test]
+    fn test_insecure_http_can_be_refused() {
+        let mut client = Client::new("http://shortcuts.example");
+        assert!(client.request(Method::Get, "/").is_ok());
+
+        client.set_allow_insecure_http(false);
+        let error = client
+            .request(Method::Get, "/all?access_token=secret")
+            .err()
+            .unwrap();
+        assert!(matches!(error, RequestError::InsecureUrl(_)));
+        assert!(!error.to_string().contains("secret"), "{}", error);
+        assert!(client
+            .request_url(Method::Get, "https://shortcuts.example/")
+            .is_ok());
+        assert!(client
+            .request_url(Method::Get, "http://127.0.0.1:8080/")
+            .is_ok());
+    }
+
+    #[test]
+    fn test_trace_ids() {
+        let (port, server) = serve_capture_headers();
+        let client = Client::new(&format!("http://127.0.0.1:{}", port));
+        let response = client
+            .request(Method::Get, "/")
+            .and_then(|request| request.with_trace_id("support-1"))
+            .and_then(Request::send)
+            .unwrap();
+        assert_eq!(response.request_id(), Some("support-1"));
+        let headers = server.join().unwrap();
+        assert!(headers.contains(&"X-Request-Id: support-1".to_string()));
+
+        let mut client = Client::new("https://example.com");
+        client.set_trace_ids(true);
+        let ids: Vec<String> = (0..2)
+            .map(|_| client.request(Method::Get, "/").unwrap().trace_id.unwrap())
+            .collect();
+        assert!(ids.iter().all(|id| trace::is_trace_id(id)));
+        assert_ne!(ids[0], ids[1]);
+
+        let bad_request = "HTTP/1.1 400 Bad Request\r\nX-Request-Id: srv-7\r\n\
+                           Content-Length: 0\r\nConnection: close\r\n\r\n";
+        let mut client = Client::new(&serve(vec![bad_request]));
+        client.set_trace_ids(true);
+        let error = client
+            .request(Method::Get, "/")
+            .and_then(Request::send_checked)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Request rejected with status 400, request id srv-7"
+        );
+    }
+
+    #[test]
+    fn test_prepared_request() {
+        let client = Client::new(&serve_echo());
+        let prepared = client
+            .request(Method::Post, "/shortcuts")
+            .and_then(|request| request.with_arguments("nested=false"))
+            .and_then(|request| request.with_body("g"))
+            .and_then(Request::build)
+            .unwrap();
+        assert_eq!(prepared.method(), &Method::Post);
+        assert!(prepared.url().ends_with("/shortcuts?nested=false"));
+        assert_eq!(prepared.header("user-agent"), Some("curl-http"));
+        assert_eq!(prepared.body(), Some(&b"g"[..]));
+
+        let copy = prepared.clone();
+        assert_eq!(copy, prepared);
+        let response = client.send_prepared(copy).unwrap();
+        assert_eq!(response.body(), b"POST g");
+
+        let secret = client
+            .request(Method::Post, "/shortcuts")
+            .and_then(|request| request.with_arguments("access_token=t0ken"))
+            .and_then(|request| request.with_header("Authorization", "Bearer t0ken"))
+            .and_then(|request| request.with_json_body(&serde_json::json!({"token": "t0ken"})))
+            .and_then(Request::build)
+            .unwrap();
+        let debug = format!("{:?}", secret);
+        assert!(!debug.contains("t0ken"), "{}", debug);
+        assert!(debug.contains("access_token=[REDACTED]"), "{}", debug);
+
+        let error = client
+            .request(Method::Put, "/")
+            .and_then(|request| request.with_reader(&b"streamed"[..]))
+            .and_then(Request::build)
+            .unwrap_err();
+        assert!(matches!(error, RequestError::StreamedBody));
+    }
+
+    #[test]
+    fn test_circuit_breaker() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let clock = Rc::new(testing::MockClock::new(SystemTime::now()));
+        let mut client = Client::new(&base_url);
+        client.set_clock(clock.clone());
+        client.set_circuit_breaker(2, Duration::from_secs(60), Duration::from_secs(30));
+
+        // The first failure is too long before the others to count, the third opens the circuit.
+        for advance in [61, 0, 0] {
+            let error = client.get("/").unwrap_err();
+            assert!(matches!(error, RequestError::CouldntConnect(_)));
+            clock.advance(Duration::from_secs(advance));
+        }
+        let error = client.clone().get("/").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The server seems to be down, no requests are sent for another 30s"
+        );
+
+        // The probe after the cooldown fails, so the circuit opens again.
+        clock.advance(Duration::from_secs(30));
+        let error = client.get("/").unwrap_err();
+        assert!(matches!(error, RequestError::CouldntConnect(_)));
+        let error = client.get("/").unwrap_err();
+        assert!(matches!(error, RequestError::CircuitOpen(_)));
+    }
+
+    #[test]
+    fn test_prefer() {
+        let created = "HTTP/1.1 201 Created\r\nPreference-Applied: return=minimal\r\n\
+                       Content-Length: 0\r\nConnection: close\r\n\r\n";
+        let client = Client::new(&serve(vec![created]));
+        let request = client
+            .request(Method::Post, "/shortcuts")
+            .and_then(|request| request.prefer("return=minimal"))
+            .and_then(|request| request.prefer("handling=lenient"))
+            .unwrap();
+        assert!(request.headers.contains(&(
+            "Prefer".to_string(),
+            "return=minimal, handling=lenient".to_string()
+        )));
+
+        let response = request.send().unwrap();
+        assert_eq!(response.preference_applied(), Some("return=minimal"));
+    }
+
+    #[test]
+    fn test_with_headers() {
+        let client = Client::new("https://example.com");
+        let request = client
+            .request(Method::Post, "/")
+            .and_then(|request| {
+                request.with_headers(&[
+                    ("Accept", "application/json"),
+                    ("Content-Type", "text/plain"),
+                    ("content-type", "application/json"),
+                    ("Idempotency-Key", "1"),
+                ])
+            })
+            .unwrap();
+
+        let names: Vec<&str> = request
+            .headers
+            .iter()
+            .map(|(key, _)| key.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["User-Agent", "Accept", "content-type", "Idempotency-Key"]
+        );
+    }
+
+    fn collect_lines(chunks: &[&[u8]]) -> Vec<String> {
+        let mut lines = vec![];
+        let mut buffer = LineBuffer::default();
+        for chunk in chunks {
+            buffer.push(chunk, &mut |line| lines.push(line.to_string()));
+        }
+        buffer.finish(&mut |line| lines.push(line.to_string()));
+        lines
+    }
+
+    #[test]
+    fn test_line_buffer_reassembles_split_lines() {
+        let chunks: &[&[u8]] = &[b"hel", b"lo\nwor", b"ld\r", b"\n\n", b"ta", b"il"];
+        assert_eq!(collect_lines(chunks), vec!["hello", "world", "", "tail"]);
+    }
+
+    #[test]
+    fn test_line_buffer_keeps_split_multibyte_chars() {
+        let text = "caf\u{e9}\n\u{1f600}\n".as_bytes();
+        let chunks: Vec<&[u8]> = text.chunks(1).collect();
+        assert_eq!(collect_lines(&chunks), vec!["caf\u{e9}", "\u{1f600}"]);
+    }
+
+    #[test]
+    fn test_line_buffer_without_trailing_line_break() {
+        assert_eq!(collect_lines(&[b"a\nb"]), vec!["a", "b"]);
+        assert!(collect_lines(&[b""]).is_empty());
+    }
 }
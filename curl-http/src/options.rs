@@ -0,0 +1,123 @@
+use std::convert::TryFrom;
+use std::time::Duration;
+
+use curl::easy::{Easy, IpResolve};
+
+use crate::{RequestError, Result};
+
+/// A curl option set by its name, see [`crate::Client::set_curl_string_option`]
+/// and [`crate::Client::set_curl_long_option`].
+#[derive(Clone, Debug)]
+pub(crate) enum CurlOption {
+    Proxy(String),
+    NoProxy(String),
+    Interface(String),
+    CaInfo(String),
+    CaPath(String),
+    ProxyCaInfo(String),
+    UnixSocket(String),
+    SslCipherList(String),
+    Timeout(Duration),
+    ConnectTimeout(Duration),
+    LowSpeedLimit(u32),
+    LowSpeedTime(Duration),
+    MaxRedirections(u32),
+    IpResolve(IpResolve),
+    TcpKeepalive(bool),
+    Verbose(bool),
+}
+
+impl CurlOption {
+    pub(crate) fn from_string(name: &str, value: &str) -> Result<CurlOption> {
+        let value = value.to_string();
+        Ok(match name {
+            "proxy" => CurlOption::Proxy(value),
+            "noproxy" => CurlOption::NoProxy(value),
+            "interface" => CurlOption::Interface(value),
+            "cainfo" => CurlOption::CaInfo(value),
+            "capath" => CurlOption::CaPath(value),
+            "proxy_cainfo" => CurlOption::ProxyCaInfo(value),
+            "unix_socket" => CurlOption::UnixSocket(value),
+            "ssl_cipher_list" => CurlOption::SslCipherList(value),
+            _ => return Err(RequestError::UnknownCurlOption(name.to_string())),
+        })
+    }
+
+    pub(crate) fn from_long(name: &str, value: i64) -> Result<CurlOption> {
+        let invalid = || RequestError::InvalidCurlOption(name.to_string(), value);
+        let unsigned = u32::try_from(value).map_err(|_| invalid());
+        let millis = u64::try_from(value)
+            .map(Duration::from_millis)
+            .map_err(|_| invalid());
+        let flag = match value {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid()),
+        };
+        Ok(match name {
+            "timeout_ms" => CurlOption::Timeout(millis?),
+            "connect_timeout_ms" => CurlOption::ConnectTimeout(millis?),
+            "low_speed_limit" => CurlOption::LowSpeedLimit(unsigned?),
+            "low_speed_time" => CurlOption::LowSpeedTime(Duration::from_secs(u64::from(unsigned?))),
+            "max_redirections" => CurlOption::MaxRedirections(unsigned?),
+            "ipresolve" => CurlOption::IpResolve(match value {
+                0 => IpResolve::Any,
+                1 => IpResolve::V4,
+                2 => IpResolve::V6,
+                _ => return Err(invalid()),
+            }),
+            "tcp_keepalive" => CurlOption::TcpKeepalive(flag?),
+            "verbose" => CurlOption::Verbose(flag?),
+            _ => return Err(RequestError::UnknownCurlOption(name.to_string())),
+        })
+    }
+
+    pub(crate) fn apply(&self, handle: &mut Easy) -> Result<()> {
+        match *self {
+            CurlOption::Proxy(ref value) => handle.proxy(value)?,
+            CurlOption::NoProxy(ref value) => handle.noproxy(value)?,
+            CurlOption::Interface(ref value) => handle.interface(value)?,
+            CurlOption::CaInfo(ref value) => handle.cainfo(value)?,
+            CurlOption::CaPath(ref value) => handle.capath(value)?,
+            CurlOption::ProxyCaInfo(ref value) => handle.proxy_cainfo(value)?,
+            CurlOption::UnixSocket(ref value) => handle.unix_socket(value)?,
+            CurlOption::SslCipherList(ref value) => handle.ssl_cipher_list(value)?,
+            CurlOption::Timeout(value) => handle.timeout(value)?,
+            CurlOption::ConnectTimeout(value) => handle.connect_timeout(value)?,
+            CurlOption::LowSpeedLimit(value) => handle.low_speed_limit(value)?,
+            CurlOption::LowSpeedTime(value) => handle.low_speed_time(value)?,
+            CurlOption::MaxRedirections(value) => handle.max_redirections(value)?,
+            CurlOption::IpResolve(value) => handle.ip_resolve(value)?,
+            CurlOption::TcpKeepalive(value) => handle.tcp_keepalive(value)?,
+            CurlOption::Verbose(value) => handle.verbose(value)?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_curl_option_names() {
+        assert!(CurlOption::from_string("proxy", "http://proxy:3128").is_ok());
+        assert!(CurlOption::from_long("timeout_ms", 1500).is_ok());
+        assert!(matches!(
+            CurlOption::from_string("timeout_ms", "1500"),
+            Err(RequestError::UnknownCurlOption(_))
+        ));
+        assert!(matches!(
+            CurlOption::from_long("ssl_verifypeer", 0),
+            Err(RequestError::UnknownCurlOption(_))
+        ));
+        assert!(matches!(
+            CurlOption::from_long("timeout_ms", -1),
+            Err(RequestError::InvalidCurlOption(_, -1))
+        ));
+        assert!(matches!(
+            CurlOption::from_long("verbose", 2),
+            Err(RequestError::InvalidCurlOption(_, 2))
+        ));
+    }
+}
@@ -0,0 +1,131 @@
+//! Helpers which remove credentials from urls, headers and bodies before they
+//! are shown in errors or written to logs.
+
+use serde_json::Value;
+
+/// The most chars of a request body quoted in an error.
+const MAX_EXCERPT_CHARS: usize = 512;
+
+/// Keys of JSON fields whose values are never quoted.
+const SECRET_KEYS: &[&str] = &["token", "password", "secret", "authorization"];
+
+/// Headers whose values are never shown.
+const SECRET_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+];
+
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Returns the **body** with the values of secret looking JSON fields replaced.
+pub fn body(body: &[u8]) -> String {
+    match serde_json::from_slice::<Value>(body) {
+        Ok(mut value) => {
+            redact(&mut value);
+            value.to_string()
+        }
+        Err(_) => String::from_utf8_lossy(body).into_owned(),
+    }
+}
+
+/// Returns the **body** for an error message, truncated and with the values
+/// of secret looking JSON fields replaced.
+pub fn body_excerpt(body: &[u8]) -> String {
+    let text = self::body(body);
+    match text.char_indices().nth(MAX_EXCERPT_CHARS) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text,
+    }
+}
+
+/// Returns the **url** with the values of its query replaced, which may be tokens.
+pub fn url(url: &str) -> String {
+    let (base, query) = match url.split_once('?') {
+        Some(parts) => parts,
+        None => return url.to_string(),
+    };
+    let (query, fragment) = match query.split_once('#') {
+        Some((query, fragment)) => (query, Some(fragment)),
+        None => (query, None),
+    };
+    let query: Vec<String> = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) => format!("{}={}", name, REDACTED),
+            None => pair.to_string(),
+        })
+        .collect();
+    let mut redacted = format!("{}?{}", base, query.join("&"));
+    if let Some(fragment) = fragment {
+        redacted.push('#');
+        redacted.push_str(fragment);
+    }
+    redacted
+}
+
+/// Returns the **value** of the header **name**, unless it is a credential.
+pub fn header<'a>(name: &str, value: &'a str) -> &'a str {
+    if SECRET_HEADERS
+        .iter()
+        .any(|secret| name.eq_ignore_ascii_case(secret))
+    {
+        REDACTED
+    } else {
+        value
+    }
+}
+
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                let key = key.to_ascii_lowercase();
+                if SECRET_KEYS.iter().any(|secret| key.contains(secret)) {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_body_excerpt_redacts_secrets() {
+        let body = br#"{"title":"Rust","access_token":"abc","nested":[{"Password":"p"}]}"#;
+        assert_eq!(
+            body_excerpt(body),
+            r#"{"access_token":"[REDACTED]","nested":[{"Password":"[REDACTED]"}],"title":"Rust"}"#
+        );
+    }
+
+    #[test]
+    fn test_url_and_headers_are_redacted() {
+        assert_eq!(
+            url("https://a.com/all?nested=false&access_token=abc#top"),
+            "https://a.com/all?nested=[REDACTED]&access_token=[REDACTED]#top"
+        );
+        assert_eq!(url("https://a.com/all"), "https://a.com/all");
+        assert_eq!(header("authorization", "Bearer abc"), "[REDACTED]");
+        assert_eq!(header("Cookie", "session=abc"), "[REDACTED]");
+        assert_eq!(header("Accept", "*/*"), "*/*");
+    }
+
+    #[test]
+    fn test_body_excerpt_is_truncated() {
+        let body_text = "\u{e9}".repeat(MAX_EXCERPT_CHARS + 1);
+        let excerpt = body_excerpt(body_text.as_bytes());
+        assert_eq!(excerpt.chars().count(), MAX_EXCERPT_CHARS + 3);
+        assert!(excerpt.ends_with("..."));
+        assert_eq!(body_excerpt(b"plain"), "plain");
+        assert_eq!(body(body_text.as_bytes()), body_text);
+    }
+}
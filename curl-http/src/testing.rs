@@ -0,0 +1,58 @@
+//! Helpers for deterministic tests of code which uses the [`crate::Client`].
+
+use std::cell::{Cell, RefCell};
+use std::time::{Duration, SystemTime};
+
+use crate::Clock;
+
+/// A clock which only moves when told to, sleeping advances it right away.
+pub struct MockClock {
+    now: Cell<SystemTime>,
+    sleeps: RefCell<Vec<Duration>>,
+}
+
+impl MockClock {
+    /// Start the clock at **now**.
+    pub fn new(now: SystemTime) -> MockClock {
+        MockClock {
+            now: Cell::new(now),
+            sleeps: RefCell::new(vec![]),
+        }
+    }
+
+    /// Move the clock forward by **duration**.
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+
+    /// Returns every duration slept so far.
+    pub fn sleeps(&self) -> Vec<Duration> {
+        self.sleeps.borrow().clone()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        self.now.get()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.sleeps.borrow_mut().push(duration);
+        self.advance(duration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock() {
+        let start = SystemTime::UNIX_EPOCH;
+        let clock = MockClock::new(start);
+        clock.advance(Duration::from_secs(1));
+        clock.sleep(Duration::from_millis(250));
+        assert_eq!(clock.now(), start + Duration::from_millis(1250));
+        assert_eq!(clock.sleeps(), vec![Duration::from_millis(250)]);
+    }
+}
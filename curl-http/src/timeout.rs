@@ -0,0 +1,85 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// How many of the latest request times are kept.
+const SAMPLES: usize = 20;
+
+/// The timeout is this multiple of the 95th percentile of the latest request times.
+const P95_FACTOR: u32 = 4;
+
+/// Timeouts which follow how long the latest requests took,
+/// see [`crate::Client::set_adaptive_timeout`].
+pub(crate) struct AdaptiveTimeout {
+    min: Duration,
+    max: Duration,
+    samples: RefCell<VecDeque<Duration>>,
+}
+
+impl AdaptiveTimeout {
+    pub(crate) fn new(min: Duration, max: Duration) -> AdaptiveTimeout {
+        AdaptiveTimeout {
+            min,
+            max: max.max(min),
+            samples: RefCell::new(VecDeque::with_capacity(SAMPLES)),
+        }
+    }
+
+    /// Remember how long a request took.
+    pub(crate) fn record(&self, elapsed: Duration) {
+        let mut samples = self.samples.borrow_mut();
+        if samples.len() == SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(elapsed);
+    }
+
+    /// The timeout for the next request, the maximum until a request finished.
+    pub(crate) fn current(&self) -> Duration {
+        let mut samples: Vec<Duration> = self.samples.borrow().iter().copied().collect();
+        if samples.is_empty() {
+            return self.max;
+        }
+        samples.sort();
+        let p95 = samples[(samples.len() * 95).div_ceil(100) - 1];
+        (p95 * P95_FACTOR).clamp(self.min, self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adaptive_timeout_follows_p95() {
+        let timeout = AdaptiveTimeout::new(Duration::from_secs(1), Duration::from_secs(30));
+        assert_eq!(timeout.current(), Duration::from_secs(30));
+
+        for _ in 0..19 {
+            timeout.record(Duration::from_millis(500));
+        }
+        timeout.record(Duration::from_secs(60));
+        assert_eq!(timeout.current(), Duration::from_secs(2));
+
+        timeout.record(Duration::from_millis(10));
+        timeout.record(Duration::from_secs(60));
+        assert_eq!(timeout.current(), Duration::from_secs(30));
+
+        for _ in 0..20 {
+            timeout.record(Duration::from_millis(10));
+        }
+        assert_eq!(timeout.current(), Duration::from_secs(1));
+    }
+    #[test]
+    fn test_adaptive_timeout_grows_after_timeouts() {
+        let timeout = AdaptiveTimeout::new(Duration::from_millis(50), Duration::from_secs(5));
+        timeout.record(Duration::from_millis(1));
+        assert_eq!(timeout.current(), Duration::from_millis(50));
+
+        // Every timed out request is recorded with the timeout it had.
+        for expected in [200, 800, 3200, 5000, 5000] {
+            timeout.record(timeout.current());
+            assert_eq!(timeout.current(), Duration::from_millis(expected));
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Returns a new random request id, 32 lowercase hex digits like the trace id
+/// of a W3C `traceparent`, see [`crate::Client::set_trace_ids`].
+pub(crate) fn generate_id() -> String {
+    format!("{:016x}{:016x}", random(), random())
+}
+
+/// Whether **id** can be used as the trace id of a `traceparent`.
+#[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+pub(crate) fn is_trace_id(id: &str) -> bool {
+    id.len() == 32
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+        && id.bytes().any(|b| b != b'0')
+}
+
+/// Returns a `traceparent` header value of the **trace_id** with a new parent id.
+#[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+pub(crate) fn traceparent(trace_id: &str) -> String {
+    format!("00-{}-{:016x}-01", trace_id, random() | 1)
+}
+
+/// A random number without a dependency on a random number generator: the std
+/// hasher is seeded randomly, the counter and the time make every call differ.
+fn random() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.write_u128(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+    );
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_id() {
+        let id = generate_id();
+        assert!(is_trace_id(&id));
+        assert_ne!(id, generate_id());
+        assert!(!is_trace_id("0".repeat(32).as_str()));
+        assert!(!is_trace_id("ABCDEF0123456789abcdef0123456789"));
+
+        let traceparent = traceparent(&id);
+        let parts: Vec<&str> = traceparent.split('-').collect();
+        assert_eq!(parts.len(), 4);
+        assert_eq!((parts[0], parts[1], parts[3]), ("00", id.as_str(), "01"));
+        assert_eq!(parts[2].len(), 16);
+    }
+}
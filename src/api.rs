@@ -1,20 +1,27 @@
+use std::cell::RefCell;
 use std::fmt;
+use std::io::Read;
 use std::rc::Rc;
+use std::time::Duration;
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Result};
 use clap::crate_version;
 use serde::de::DeserializeOwned;
 use serde_derive::Deserialize;
 use thiserror::Error;
 
-use curl_http::{Client, Response};
+use curl_http::{percent_encode, Client, Method, Observer, Pages, RequestError, Response, Warning};
 
+use crate::config::Config;
+use crate::har::HarRecorder;
 use crate::models::*;
+use crate::profile::Profiles;
+use crate::store;
 
 const API_URL: &str = "https://api.anyshortcut.com";
 
 thread_local! {
-    static API: Rc<Api> = Rc::new(Api::new());
+    static API: RefCell<Option<Rc<Api>>> = const { RefCell::new(None) };
 }
 
 #[derive(Deserialize, Debug)]
@@ -24,6 +31,13 @@ pub struct ApiResponse<T> {
     pub message: String,
 }
 
+/// The body of a request sent with [`Api::raw`].
+pub enum RawBody<'a> {
+    Data(Vec<u8>),
+    /// Streamed while sending, e.g. from stdin.
+    Reader(Box<dyn Read + 'a>),
+}
+
 #[derive(Debug, Error)]
 pub struct ApiError {
     pub code: u32,
@@ -70,49 +84,363 @@ impl<T> From<ApiResponse<T>> for ApiError {
     }
 }
 
+/// Prints the warnings of the http client to stderr.
+struct WarningPrinter;
+
+impl Observer for WarningPrinter {
+    fn on_warning(&self, warning: &Warning) {
+        eprintln!("Warning: {}", warning);
+    }
+}
+
 pub struct Api {
+    api_url: String,
     client: Client,
 }
 
 impl Api {
-    pub fn new() -> Api {
-        let mut client = Client::new(API_URL);
+    pub fn new() -> Result<Api> {
+        let config = Config::current();
+        let profile = Profiles::active()?;
+        let api_url = config.api_url.as_ref().or(profile.api_url.as_ref());
+        let api_url = api_url.map_or(API_URL, String::as_str).to_string();
+        let mut client = Client::new(&api_url);
+        // The library allows plain http, the CLI only with --insecure-http.
+        client.set_allow_insecure_http(config.insecure_http);
         client.set_user_agent(&format!("anyshortcut-cli/{}", crate_version!()));
-        Api { client }
+        client.add_observer(Rc::new(WarningPrinter));
+        if let Some(timeout) = config.timeout {
+            client.set_timeout(timeout);
+        }
+        client.set_retries(config.retries);
+        client.set_offline(config.offline);
+        let store_dir = store::get_store_directory()?;
+        // Lets --offline answer from the responses of earlier runs.
+        client.set_http_cache(store_dir.join("cache"));
+        // Kept in a file, because a single run rarely sends enough requests to open it.
+        client.set_circuit_breaker(5, Duration::from_secs(60), Duration::from_secs(30));
+        client.set_circuit_breaker_file(store_dir.join("circuit"));
+        client.set_trace_ids(true);
+        if let Some(ref doh_url) = config.doh_url {
+            client.set_doh_url(doh_url)?;
+        }
+        if let Some(ref path) = config.har_path {
+            client.add_observer(Rc::new(HarRecorder::new(path.clone())));
+        }
+        Ok(Api { api_url, client })
     }
 
-    /// Returns the current api for the thread.
-    pub fn get_current() -> Rc<Api> {
-        API.with(std::clone::Clone::clone)
+    /// Returns the current api for the thread, creating it on first use.
+    pub fn get_current() -> Result<Rc<Api>> {
+        API.with(|api| {
+            let mut api = api.borrow_mut();
+            if api.is_none() {
+                *api = Some(Rc::new(Api::new()?));
+            }
+            Ok(api.as_ref().map(Rc::clone).unwrap())
+        })
+    }
+
+    /// The url of the API server which is used.
+    pub fn api_url(&self) -> &str {
+        &self.api_url
+    }
+
+    /// Request the root of the API server to see whether it can be reached at all.
+    ///
+    /// It doesn't warn about a clock skew, the caller compares the `Date` header itself.
+    pub fn ping(&self) -> curl_http::Result<Response> {
+        let mut client = self.client.clone();
+        client.set_max_clock_skew(Duration::MAX);
+        client.get("/")
+    }
+
+    /// How far the local clock may differ from the server one.
+    pub fn max_clock_skew(&self) -> Duration {
+        self.client.max_clock_skew()
     }
 
     pub fn login_with_access_token(&self, access_token: &str) -> Result<serde_json::Value> {
-        let response = self
-            .client
-            .get(&format!("/user/login?access_token={}", access_token))?;
+        let response = self.get(&format!(
+            "/user/login?access_token={}",
+            percent_encode(access_token)
+        ))?;
         self.handle_http_response(&response)
     }
 
     pub fn get_all_shortcuts(&self) -> Result<ShortcutData> {
-        let access_token = Meta::get_token();
-        let response = self.client.get(&format!(
+        let access_token = Meta::resolve_token()?.unwrap_or_default();
+        let response = self.get(&format!(
             "/shortcuts/all?nested=false&access_token={}",
-            access_token
+            percent_encode(&access_token)
         ))?;
         self.handle_http_response(&response)
     }
 
+    /// Fetch the shortcut of the **primary_key**, or of the **secondary_key** in its domain.
+    ///
+    /// The API has no endpoint for one shortcut, so all of them are fetched.
+    pub fn get_shortcut(
+        &self,
+        primary_key: &str,
+        secondary_key: Option<&str>,
+    ) -> Result<Option<Shortcut>> {
+        let data = self.get_all_shortcuts()?;
+        Ok(data.find(primary_key, secondary_key).cloned())
+    }
+
+    /// Iterate over all shortcuts, fetching them page by page as the iteration goes.
+    pub fn shortcuts_iter(&self) -> Result<ShortcutIter<'_>> {
+        let access_token = Meta::resolve_token()?.unwrap_or_default();
+        Ok(ShortcutIter {
+            api: self,
+            pages: self.client.pages(&format!(
+                "/shortcuts/all?nested=false&access_token={}",
+                percent_encode(&access_token)
+            )),
+            shortcuts: vec![].into_iter(),
+            done: false,
+        })
+    }
+
+    /// Send any request to the API server, with the access token of the login added
+    /// to the query unless the **endpoint** has one already.
+    pub fn raw<'a>(
+        &'a self,
+        method: Method,
+        endpoint: &str,
+        headers: &[(&str, &str)],
+        body: Option<RawBody<'a>>,
+    ) -> Result<Response> {
+        let mut endpoint = endpoint.to_string();
+        match Meta::resolve_token()? {
+            Some(token) if !endpoint.contains("access_token=") => {
+                let separator = if endpoint.contains('?') { '&' } else { '?' };
+                endpoint = format!(
+                    "{}{}access_token={}",
+                    endpoint,
+                    separator,
+                    percent_encode(&token)
+                );
+            }
+            _ => {}
+        }
+        let mut request = self
+            .client
+            .request(method, &endpoint)
+            .and_then(|request| request.with_headers(headers))
+            .map_err(request_error)?;
+        request = match body {
+            Some(RawBody::Data(data)) => request.with_body(data),
+            Some(RawBody::Reader(reader)) => request.with_reader(reader),
+            None => Ok(request),
+        }
+        .map_err(request_error)?;
+        request.send().map_err(request_error)
+    }
+
+    fn get(&self, endpoint: &str) -> Result<Response> {
+        self.client.get(endpoint).map_err(request_error)
+    }
+
     /// Handle http response internally to return correct api error according to api response code.
     fn handle_http_response<T: DeserializeOwned>(&self, response: &Response) -> Result<T> {
+        // An HTML error page of a proxy is named as such instead of as invalid JSON.
+        response.assert_content_type("application/json")?;
         let api_response = response.deserialize::<ApiResponse<serde_json::Value>>()?;
         match api_response.code {
             200 => {
                 let response = response.deserialize::<ApiResponse<T>>()?;
                 Ok(response.data)
             }
-            1000 => Err(ApiError::from(api_response)).context(ApiErrorKind::AccessTokenRequired),
-            1001 | 1002 => Err(ApiError::from(api_response)).context(ApiErrorKind::InvalidToken),
-            _ => Err(ApiError::from(api_response)).context(ApiErrorKind::UnknownError),
+            _ => Err(api_error(
+                ApiError::from(api_response),
+                response.request_id(),
+            )),
+        }
+    }
+}
+
+/// Attach the kind of the error according to the api response code.
+///
+/// Unknown errors name the **request_id**, so the support can find the request
+/// in the server logs.
+fn api_error(error: ApiError, request_id: Option<&str>) -> anyhow::Error {
+    let kind = match error.code {
+        1000 => ApiErrorKind::AccessTokenRequired,
+        1001 | 1002 => ApiErrorKind::InvalidToken,
+        _ => ApiErrorKind::UnknownError,
+    };
+    let error = anyhow::Error::new(error).context(kind);
+    match request_id {
+        Some(id) if kind == ApiErrorKind::UnknownError => {
+            error.context(format!("{} Request id: {}", kind, id))
+        }
+        _ => error,
+    }
+}
+
+/// Add a hint on how to get around errors caused by the command line options.
+fn request_error(error: RequestError) -> anyhow::Error {
+    match error {
+        RequestError::InsecureUrl(_) => {
+            anyhow!("{} Pass --insecure-http to allow it anyway.", error)
+        }
+        RequestError::Offline(_) => {
+            anyhow!("{}, run it again without --offline.", error)
+        }
+        error => error.into(),
+    }
+}
+
+/// Iterator over all shortcuts of the user, see [`Api::shortcuts_iter`].
+///
+/// With the `streaming` feature the shortcuts are decoded one by one while a page
+/// downloads, so neither the raw body nor its JSON tree is kept in memory.
+///
+/// Failures are returned as `Err` items. The iteration ends after an api error,
+/// and after the first transport error unless [`ShortcutIter::stop_on_error`] is turned off.
+pub struct ShortcutIter<'a> {
+    #[cfg_attr(feature = "streaming", allow(dead_code))]
+    api: &'a Api,
+    pages: Pages<'a>,
+    shortcuts: std::vec::IntoIter<Shortcut>,
+    done: bool,
+}
+
+impl<'a> ShortcutIter<'a> {
+    /// Whether to stop after the first transport error. Default is `true`,
+    /// otherwise the failed page is requested again up to 3 times in a row.
+    #[allow(unused)]
+    pub fn stop_on_error(mut self, stop: bool) -> ShortcutIter<'a> {
+        self.pages = self.pages.stop_on_error(stop);
+        self
+    }
+}
+
+impl Iterator for ShortcutIter<'_> {
+    type Item = Result<Shortcut>;
+
+    fn next(&mut self) -> Option<Result<Shortcut>> {
+        loop {
+            if let Some(shortcut) = self.shortcuts.next() {
+                return Some(Ok(shortcut));
+            }
+            if self.done {
+                return None;
+            }
+
+            match self.next_page()? {
+                Ok(shortcuts) => self.shortcuts = shortcuts.into_iter(),
+                Err(error) => return Some(Err(error)),
+            }
         }
     }
 }
+
+impl ShortcutIter<'_> {
+    #[cfg(not(feature = "streaming"))]
+    fn next_page(&mut self) -> Option<Result<Vec<Shortcut>>> {
+        let response = match self.pages.next()? {
+            Ok(response) => response,
+            Err(error) => return Some(Err(request_error(error))),
+        };
+        let page = self.api.handle_http_response::<ShortcutData>(&response);
+        self.done = page.is_err();
+        Some(page.map(ShortcutData::into_shortcuts))
+    }
+
+    #[cfg(feature = "streaming")]
+    fn next_page(&mut self) -> Option<Result<Vec<Shortcut>>> {
+        const PATTERNS: &[&str] = &[
+            "/code",
+            "/message",
+            "/data/primary/*",
+            "/data/secondary/*/*",
+        ];
+        let mut code = None;
+        let mut message = String::new();
+        let mut shortcuts = vec![];
+        let mut invalid = None;
+        let response = self.pages.next_json_values(PATTERNS, |pattern, value| {
+            match pattern {
+                0 => code = value.as_u64(),
+                1 => message = value.as_str().unwrap_or_default().to_string(),
+                _ => match serde_json::from_value(value) {
+                    Ok(shortcut) => shortcuts.push(shortcut),
+                    Err(error) => {
+                        invalid = Some(error);
+                        // The page can't be used, so the rest isn't downloaded.
+                        return Err(RequestError::InvalidJson);
+                    }
+                },
+            }
+            Ok(())
+        });
+        let response = match (response?, invalid) {
+            (_, Some(error)) => {
+                self.done = true;
+                return Some(Err(error.into()));
+            }
+            (Ok(response), None) => response,
+            (Err(error), None) => return Some(Err(request_error(error))),
+        };
+
+        let page = if response.failed() {
+            Err(RequestError::RequestFailed.into())
+        } else {
+            match code {
+                Some(200) => Ok(shortcuts),
+                code => Err(api_error(
+                    ApiError {
+                        code: code.unwrap_or_default() as u32,
+                        message,
+                    },
+                    response.request_id(),
+                )),
+            }
+        };
+        self.done = page.is_err();
+        Some(page)
+    }
+}
+
+#[cfg(all(test, feature = "streaming"))]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_invalid_shortcut_aborts_the_page() {
+        // Less body than announced, a transfer which is not aborted fails as cut off.
+        let response = "HTTP/1.1 200 OK\r\nContent-Length: 1000\r\nConnection: close\r\n\r\n\
+                        {\"code\": 200, \"data\": {\"primary\": [{\"id\": \"one\"}, {";
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let api_url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+
+        let api = Api {
+            client: Client::new(&api_url),
+            api_url,
+        };
+        let mut shortcuts = ShortcutIter {
+            api: &api,
+            pages: api.client.pages("/shortcuts/all"),
+            shortcuts: vec![].into_iter(),
+            done: false,
+        };
+        let error = shortcuts.next().unwrap().unwrap_err();
+        assert!(error.is::<serde_json::Error>(), "{}", error);
+        assert!(shortcuts.next().is_none());
+    }
+}
@@ -1,5 +1,6 @@
 use clap::{crate_description, crate_name, crate_version};
 use clap::{App, AppSettings, Arg, SubCommand};
+use std::time::Duration;
 
 pub fn build_cli() -> App<'static, 'static> {
     App::new(crate_name!())
@@ -23,6 +24,85 @@ pub fn build_cli() -> App<'static, 'static> {
                 .help("Use secondary shortcut key (A~Z|0~9) to open the url.")
                 .index(2)
                 .validator(validate_secondary_key),
+            Arg::with_name("color")
+                .long("color")
+                .value_name("WHEN")
+                .help("When to colorize the output, auto colorizes only in a terminal without NO_COLOR set.")
+                .possible_values(&["auto", "always", "never"])
+                .default_value("auto")
+                .global(true),
+            Arg::with_name("api_url")
+                .long("api-url")
+                .value_name("URL")
+                .help("Use another API server, e.g. for testing.")
+                .takes_value(true)
+                .env("ANYSHORTCUT_API_URL")
+                .hide_env_values(true)
+                .global(true),
+            Arg::with_name("profile")
+                .long("profile")
+                .value_name("NAME")
+                .help("Use this profile instead of the one selected with `profile use`.")
+                .takes_value(true)
+                .env("ANYSHORTCUT_PROFILE")
+                .validator(validate_profile_name)
+                .global(true),
+            Arg::with_name("insecure_http")
+                .long("insecure-http")
+                .help("Allow a plain http API url, which sends your token in cleartext.")
+                .global(true),
+            Arg::with_name("har")
+                .long("har")
+                .value_name("PATH")
+                .help("Record all HTTP requests and responses into a HAR file.")
+                .takes_value(true)
+                .global(true),
+            Arg::with_name("doh")
+                .long("doh")
+                .value_name("URL")
+                .help("Resolve host names through the given DNS-over-HTTPS server (https only).")
+                .takes_value(true)
+                .global(true),
+            Arg::with_name("token_file")
+                .long("token-file")
+                .value_name("PATH")
+                .help("Read the token from this file instead of using the one of the login, e.g. a mounted secret.")
+                .takes_value(true)
+                .env("ANYSHORTCUT_TOKEN_FILE")
+                .global(true),
+            Arg::with_name("offline")
+                .long("offline")
+                .visible_alias("no-network")
+                .help("Never touch the network, only work with the synced shortcuts.")
+                .global(true),
+            Arg::with_name("dry_run")
+                .long("dry-run")
+                .help("Show what sync or logout would change without changing anything.")
+                .global(true),
+            Arg::with_name("output_file")
+                .long("output-file")
+                .value_name("PATH")
+                .help("Write the output of list, search or profile list into a file instead of stdout.")
+                .takes_value(true)
+                .global(true),
+            Arg::with_name("force")
+                .long("force")
+                .help("Overwrite an existing --output-file.")
+                .global(true),
+            Arg::with_name("timeout")
+                .long("timeout")
+                .value_name("DURATION")
+                .help("Give up on a request after this long, e.g. 30s, 500ms or 2m.")
+                .takes_value(true)
+                .validator(validate_duration)
+                .global(true),
+            Arg::with_name("retries")
+                .long("retries")
+                .value_name("COUNT")
+                .help("Retry failed requests this many times.")
+                .takes_value(true)
+                .validator(validate_retries)
+                .global(true),
         ])
         .subcommand(
             SubCommand::with_name("login")
@@ -36,7 +116,23 @@ pub fn build_cli() -> App<'static, 'static> {
         )
         .subcommand(
             SubCommand::with_name("sync")
-                .about("Sync all shortcuts after login."),
+                .about("Sync all shortcuts after login.")
+                .arg(
+                    Arg::with_name("diff")
+                        .long("diff")
+                        .help("Print which shortcuts are added, removed or changed before storing them."),
+                )
+                .arg(
+                    Arg::with_name("diff_only")
+                        .long("diff-only")
+                        .conflicts_with("diff")
+                        .help("Only print the changes, without storing them."),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Print the changes as JSON, e.g. for other tools, implies --diff."),
+                ),
         )
         .subcommand(
             SubCommand::with_name("list")
@@ -60,14 +156,236 @@ pub fn build_cli() -> App<'static, 'static> {
                         .long("compound")
                         .short("c")
                         .help("List all compound shortcuts."),
+                )
+                .arg(
+                    Arg::with_name("sort")
+                        .long("sort")
+                        .value_name("FIELD")
+                        .help("Sort by key, title, created (newest first) or opens (most opened first).")
+                        .possible_values(&["key", "title", "created", "opens"]),
+                )
+                .arg(
+                    Arg::with_name("reverse")
+                        .long("reverse")
+                        .requires("sort")
+                        .help("Sort the other way round."),
+                )
+                .arg(
+                    Arg::with_name("limit")
+                        .long("limit")
+                        .value_name("COUNT")
+                        .help("List at most this many shortcuts, of every domain with --secondary.")
+                        .validator(validate_limit),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help("Print the shortcuts pretty, or as tab-separated --columns with a header row.")
+                        .possible_values(&["pretty", "tsv"])
+                        .default_value("pretty"),
+                )
+                .arg(
+                    Arg::with_name("columns")
+                        .long("columns")
+                        .value_name("COLUMNS")
+                        .help("The comma-separated columns of --format tsv, of id, key, url, title, comment, domain, open_times and created_time.")
+                        .default_value("key,url,title")
+                        .validator(validate_columns),
+                )
+                .arg(
+                    Arg::with_name("no_header")
+                        .long("no-header")
+                        .help("Leave out the header row of --format tsv."),
+                )
+                .args(&field_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("get")
+                .about("Show one shortcut by its key, like opening it would.")
+                .arg(
+                    Arg::with_name("primary_key")
+                        .value_name("PRIMARY_KEY | COMPOUND_KEY")
+                        .required(true)
+                        .validator(validate_primary_key),
+                )
+                .arg(
+                    Arg::with_name("secondary_key")
+                        .value_name("SECONDARY_KEY")
+                        .validator(validate_secondary_key),
+                )
+                .arg(
+                    Arg::with_name("remote")
+                        .long("remote")
+                        .short("r")
+                        .help("Get the shortcut from the server instead of the synced ones."),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Print the shortcut as JSON."),
+                )
+                .args(&field_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("search")
+                .about("Fuzzy search shortcuts by title, url, domain or comment.")
+                .arg(
+                    Arg::with_name("query")
+                        .value_name("QUERY")
+                        .help("The text to search for.")
+                        .required(true)
+                        .empty_values(false),
+                )
+                .arg(
+                    Arg::with_name("remote")
+                        .long("remote")
+                        .short("r")
+                        .help("Search the shortcuts on the server instead of the synced ones."),
+                )
+                .arg(
+                    Arg::with_name("open_first")
+                        .long("open-first")
+                        .short("o")
+                        .help("Open the best match right away."),
+                )
+                .args(&field_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("profile")
+                .about("Manage profiles, e.g. for a work and a personal account.")
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .about("List all profiles, the selected one is marked with *."),
+                )
+                .subcommand(
+                    SubCommand::with_name("use")
+                        .about("Select the profile to use from now on.")
+                        .arg(
+                            Arg::with_name("name")
+                                .value_name("NAME")
+                                .required(true)
+                                .validator(validate_profile_name),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("add")
+                        .about("Add a profile, then login with `--profile NAME login`.")
+                        .arg(
+                            Arg::with_name("name")
+                                .value_name("NAME")
+                                .required(true)
+                                .validator(validate_profile_name),
+                        )
+                        .arg(
+                            Arg::with_name("base_url")
+                                .long("base-url")
+                                .value_name("URL")
+                                .help("The API server of the profile, the official one if not given.")
+                                .takes_value(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("doctor")
+                .about("Check the storage, network, TLS, token and clock, e.g. before reporting a bug."),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Print all shortcuts of the server, e.g. for a backup or for other tools.")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help("Print one JSON array, or one JSON object per line with ndjson.")
+                        .possible_values(&["json", "ndjson"])
+                        .default_value("json"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("raw")
+                .about("Send any request to the API server with your login, e.g. to try an endpoint.")
+                .arg(
+                    Arg::with_name("method")
+                        .value_name("METHOD")
+                        .help("The HTTP method, e.g. GET or POST.")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("path")
+                        .value_name("PATH")
+                        .help("The path on the API server, e.g. /shortcuts/all.")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("data")
+                        .long("data")
+                        .short("d")
+                        .value_name("DATA")
+                        .help("Send this request body, - reads it from stdin.")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("header")
+                        .long("header")
+                        .short("H")
+                        .value_name("NAME: VALUE")
+                        .help("Add a request header, can be given several times.")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .validator(validate_header),
+                )
+                .arg(
+                    Arg::with_name("include")
+                        .long("include")
+                        .short("i")
+                        .help("Print the status line and the response headers too."),
+                )
+                .arg(
+                    Arg::with_name("fail")
+                        .long("fail")
+                        .short("f")
+                        .help("Exit with an error if the status is 4xx or 5xx."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("version")
+                .about("Show the versions of anyshortcut and libcurl for bug reports.")
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Print the versions as JSON."),
                 ),
         )
         .subcommand(
             SubCommand::with_name("logout")
-                .about("Logout and clean local data."),
+                .about("Logout and clean local data.")
+                .arg(
+                    Arg::with_name("yes")
+                        .long("yes")
+                        .short("y")
+                        .help("Delete the local shortcuts without asking."),
+                ),
         )
 }
 
+/// The `--field` options of the commands which print shortcuts.
+fn field_args() -> [Arg<'static, 'static>; 2] {
+    [
+        Arg::with_name("field")
+            .long("field")
+            .value_name("PATH")
+            .help("Print only this field of every shortcut, e.g. url or title.")
+            .takes_value(true)
+            .empty_values(false),
+        Arg::with_name("require_field")
+            .long("require-field")
+            .requires("field")
+            .help("Fail if a shortcut has no --field instead of printing an empty line."),
+    ]
+}
+
 /// Validate primary key format.
 /// Including one-letter primary key and two-letters compound key.
 fn validate_primary_key(key: String) -> Result<(), String> {
@@ -91,6 +409,106 @@ fn validate_secondary_key(key: String) -> Result<(), String> {
     }
 }
 
+/// Validate a `Name: value` request header.
+fn validate_header(header: String) -> Result<(), String> {
+    match header.split_once(':') {
+        Some((name, _)) if !name.trim().is_empty() => Ok(()),
+        _ => Err(String::from("The header must look like `Name: value`.")),
+    }
+}
+
+/// Validate a profile name, which is used as directory name.
+fn validate_profile_name(name: String) -> Result<(), String> {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(())
+    } else {
+        Err(String::from(
+            "Invalid profile name, use only letters, digits, - and _",
+        ))
+    }
+}
+
+/// Parse a human duration like `30s`, `500ms`, `2m` or `1h`, plain numbers are seconds.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let invalid = || format!("Invalid duration '{}', expected e.g. 30s", value);
+    let number: u64 = number.parse().map_err(|_| invalid())?;
+    let seconds = |factor: u64| number.checked_mul(factor).ok_or_else(invalid);
+    let duration = match unit {
+        "ms" => Duration::from_millis(number),
+        "" | "s" => Duration::from_secs(number),
+        "m" => Duration::from_secs(seconds(60)?),
+        "h" => Duration::from_secs(seconds(60 * 60)?),
+        _ => {
+            return Err(format!(
+                "Invalid duration unit '{}', use ms, s, m or h",
+                unit
+            ))
+        }
+    };
+    if duration == Duration::from_secs(0) {
+        return Err(String::from("The duration must be greater than zero"));
+    }
+    Ok(duration)
+}
+
+fn validate_duration(value: String) -> Result<(), String> {
+    parse_duration(&value).map(|_| ())
+}
+
+/// Validate the `--limit` of `list`, which must be a positive number.
+fn validate_limit(value: String) -> Result<(), String> {
+    match value.parse::<usize>() {
+        Ok(limit) if limit > 0 => Ok(()),
+        _ => Err(format!(
+            "Invalid limit '{}', expected a positive number",
+            value
+        )),
+    }
+}
+
+/// Validate the `--columns` of `list`, which must be fields of a shortcut.
+fn validate_columns(value: String) -> Result<(), String> {
+    const COLUMNS: &[&str] = &[
+        "id",
+        "key",
+        "url",
+        "title",
+        "comment",
+        "domain",
+        "open_times",
+        "created_time",
+    ];
+    match value
+        .split(',')
+        .map(str::trim)
+        .find(|column| !COLUMNS.contains(column))
+    {
+        Some(column) => Err(format!(
+            "Unknown column '{}', use {}",
+            column,
+            COLUMNS.join(", ")
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Validate the retry count, more than 10 retries would wait for minutes.
+fn validate_retries(value: String) -> Result<(), String> {
+    match value.parse::<u32>() {
+        Ok(retries) if retries <= 10 => Ok(()),
+        Ok(_) => Err(String::from("At most 10 retries are allowed")),
+        Err(_) => Err(format!("Invalid retry count '{}'", value)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use clap::ErrorKind;
@@ -100,7 +518,7 @@ mod tests {
 
     #[test]
     fn test_args_conflict() {
-        for pair in vec!["-p", "-c", "-s"].iter().combinations(2) {
+        for pair in ["-p", "-c", "-s"].iter().combinations(2) {
             let mut args: Vec<&str> = vec![crate_name!(), "list"];
             args.extend(pair);
             let res = build_cli().get_matches_from_safe(args);
@@ -122,5 +540,36 @@ mod tests {
         let args = vec![crate_name!(), "a*"];
         let res = build_cli().get_matches_from_safe(args);
         assert!(res.is_err());
+
+        let args = vec![crate_name!(), "raw", "GET", "/", "-H", "Accept: text/plain"];
+        assert!(build_cli().get_matches_from_safe(args).is_ok());
+        let args = vec![crate_name!(), "raw", "GET", "/", "-H", "Accept"];
+        assert!(build_cli().get_matches_from_safe(args).is_err());
+
+        let args = vec![crate_name!(), "list", "-p", "--columns", "key, open_times"];
+        assert!(build_cli().get_matches_from_safe(args).is_ok());
+        let args = vec![crate_name!(), "list", "-p", "--columns", "key,category"];
+        assert!(build_cli().get_matches_from_safe(args).is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("0s").is_err());
+        assert!(parse_duration("5 s").is_err());
+        assert!(parse_duration("1d").is_err());
+        assert_eq!(
+            parse_duration("999999999999999999h"),
+            Err(String::from(
+                "Invalid duration '999999999999999999h', expected e.g. 30s"
+            ))
+        );
+
+        let args = vec![crate_name!(), "--retries", "11", "sync"];
+        assert!(build_cli().get_matches_from_safe(args).is_err());
     }
 }
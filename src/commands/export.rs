@@ -0,0 +1,121 @@
+use anyhow::Result;
+use clap::ArgMatches;
+
+use crate::api::Api;
+use crate::models::Shortcut;
+use crate::utils::output;
+
+/// How the shortcuts are written by `export`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Format {
+    /// One JSON array.
+    Json,
+    /// One JSON object per line.
+    Ndjson,
+}
+
+impl Format {
+    /// Returns the text to write before the first shortcut.
+    fn begin(self) -> &'static str {
+        match self {
+            Format::Json => "[",
+            Format::Ndjson => "",
+        }
+    }
+
+    /// Returns the text of the **index**th **shortcut**.
+    fn item(self, index: usize, shortcut: &Shortcut) -> Result<String> {
+        let json = serde_json::to_string(shortcut)?;
+        Ok(match self {
+            Format::Json if index == 0 => format!("\n  {}", json),
+            Format::Json => format!(",\n  {}", json),
+            Format::Ndjson => format!("{}\n", json),
+        })
+    }
+
+    /// Returns the text to write after the last of **count** shortcuts.
+    fn end(self, count: usize) -> &'static str {
+        match self {
+            Format::Json if count == 0 => "]\n",
+            Format::Json => "\n]\n",
+            Format::Ndjson => "",
+        }
+    }
+}
+
+/// Write all shortcuts of the server as they arrive, so even a huge account
+/// is never held in memory as a whole. If a page fails, the partial
+/// `--output-file` is removed again.
+pub fn execute(matches: &ArgMatches) -> Result<()> {
+    let format = match matches.value_of("format") {
+        Some("ndjson") => Format::Ndjson,
+        _ => Format::Json,
+    };
+    let api = Api::get_current()?;
+    let shortcuts = api.shortcuts_iter()?;
+
+    output::print(format_args!("{}", format.begin()));
+    let mut count = 0;
+    for shortcut in shortcuts {
+        output::print(format_args!("{}", format.item(count, &shortcut?)?));
+        count += 1;
+    }
+    output::print(format_args!("{}", format.end(count)));
+
+    if output::is_redirected() {
+        println!("Exported {} shortcuts.", count);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export(format: Format, shortcuts: &[Shortcut]) -> String {
+        let mut text = format.begin().to_string();
+        for (index, shortcut) in shortcuts.iter().enumerate() {
+            text.push_str(&format.item(index, shortcut).unwrap());
+        }
+        text.push_str(format.end(shortcuts.len()));
+        text
+    }
+
+    #[test]
+    fn test_export_round_trip() {
+        let shortcuts = vec![
+            Shortcut::test(1, "g")
+                .with_url("https://google.com")
+                .with_title("Google")
+                .with_domain("google.com")
+                .with_open_times(3),
+            Shortcut::test(2, "d")
+                .with_url("https://docs.rs")
+                .with_title("Docs, \"rs\"\n")
+                .with_comment("x")
+                .with_domain("docs.rs")
+                .with_timestamp(1),
+        ];
+        let expected = serde_json::to_value(&shortcuts).unwrap();
+
+        let json = export(Format::Json, &shortcuts);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+            expected
+        );
+        let empty = export(Format::Json, &[]);
+        assert_eq!(
+            serde_json::from_str::<Vec<Shortcut>>(&empty).unwrap().len(),
+            0
+        );
+
+        let ndjson = export(Format::Ndjson, &shortcuts);
+        assert_eq!(ndjson.lines().count(), 2);
+        let lines: Vec<serde_json::Value> = ndjson
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(serde_json::Value::from(lines), expected);
+        assert_eq!(export(Format::Ndjson, &[]), "");
+    }
+}
@@ -0,0 +1,116 @@
+use std::cmp::Reverse;
+
+use anyhow::{bail, Result};
+use clap::ArgMatches;
+
+use crate::api::Api;
+use crate::commands::print_fields;
+use crate::models::{Meta, Shortcut, ShortcutManager};
+use crate::utils::fuzzy::fuzzy_match;
+use crate::utils::output::outln;
+
+/// How many similar shortcuts an unknown key suggests.
+const SUGGESTIONS: usize = 3;
+
+pub fn execute(matches: &ArgMatches) -> Result<()> {
+    let primary_key = matches.value_of("primary_key").unwrap_or_default();
+    let secondary_key = matches.value_of("secondary_key");
+    let shortcut = if matches.is_present("remote") {
+        if Meta::resolve_token()?.is_none() {
+            bail!("Can't get remotely, you are not in login state. Please run login first.");
+        }
+        Api::get_current()?.get_shortcut(primary_key, secondary_key)?
+    } else {
+        match secondary_key {
+            Some(secondary_key) => {
+                ShortcutManager::get_secondary_by_keys(primary_key, secondary_key)
+            }
+            None => ShortcutManager::get_primary_by_key(primary_key),
+        }
+    };
+
+    let shortcut = match shortcut {
+        Some(shortcut) => shortcut,
+        None => {
+            let key = match secondary_key {
+                Some(secondary_key) => format!("{} {}", primary_key, secondary_key),
+                None => primary_key.to_string(),
+            };
+            bail!(not_found(&key, &ShortcutManager::get_all_shortcuts()));
+        }
+    };
+    if print_fields(matches, Some(&shortcut))? {
+        return Ok(());
+    }
+    if matches.is_present("json") {
+        outln!("{}", serde_json::to_string_pretty(&shortcut)?);
+    } else {
+        shortcut.pretty_print();
+    }
+    Ok(())
+}
+
+/// The message for an unknown **key**, suggesting the synced **shortcuts** whose
+/// title or domain looks like it, e.g. `GitHub` for `gh`.
+fn not_found(key: &str, shortcuts: &[Shortcut]) -> String {
+    let mut similar: Vec<(i64, &Shortcut)> = shortcuts
+        .iter()
+        .filter_map(|shortcut| {
+            let score = [&shortcut.title, &shortcut.domain]
+                .iter()
+                .filter_map(|text| fuzzy_match(key, text))
+                .map(|matched| matched.score)
+                .max()?;
+            Some((score, shortcut))
+        })
+        .collect();
+    similar.sort_by_key(|&(score, _)| Reverse(score));
+
+    let message = format!("No shortcut with key '{}'", key.to_uppercase());
+    if similar.is_empty() {
+        return format!("{}.", message);
+    }
+    let suggestions: Vec<String> = similar
+        .iter()
+        .take(SUGGESTIONS)
+        .map(|(_, shortcut)| format!("{} ({})", shortcut.key.to_uppercase(), shortcut.title))
+        .collect();
+    format!("{}, did you mean {}?", message, suggestions.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_not_found() {
+        let shortcuts = vec![
+            Shortcut::test(1, "g")
+                .with_title("GitHub")
+                .with_domain("github.com"),
+            Shortcut::test(1, "r")
+                .with_title("Rust")
+                .with_domain("rust-lang.org"),
+            Shortcut::test(1, "t")
+                .with_title("Go Into Town")
+                .with_domain("town.com"),
+            Shortcut::test(1, "d")
+                .with_title("Digital Ocean")
+                .with_domain("digitalocean.com"),
+            Shortcut::test(1, "l")
+                .with_title("GitLab")
+                .with_domain("gitlab.com"),
+        ];
+        assert_eq!(
+            not_found("rst", &shortcuts),
+            "No shortcut with key 'RST', did you mean R (Rust)?"
+        );
+        // The best three, equal scores keep their order.
+        assert_eq!(
+            not_found("git", &shortcuts),
+            "No shortcut with key 'GIT', did you mean G (GitHub), L (GitLab), D (Digital Ocean)?"
+        );
+        assert_eq!(not_found("xyz", &shortcuts), "No shortcut with key 'XYZ'.");
+        assert_eq!(not_found("g m", &[]), "No shortcut with key 'G M'.");
+    }
+}
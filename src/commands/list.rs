@@ -1,21 +1,29 @@
 use ansi_term::Color::{Cyan, Red};
 use clap::ArgMatches;
 
+use crate::commands::{print_fields, print_tsv};
 use crate::models::{Shortcut, ShortcutManager};
+use crate::utils::color::style;
+use crate::utils::output::outln;
+use crate::utils::sort;
 
 pub fn execute(matches: &ArgMatches) -> anyhow::Result<()> {
     if matches.is_present("primary") {
         if let Some(shortcuts) = ShortcutManager::get_primary_shortcuts() {
-            shortcuts.iter().for_each(Shortcut::pretty_print);
+            let listed = arrange(matches, &shortcuts);
+            if print_fields(matches, &listed)? || print_tsv(matches, &listed)? {
+                return Ok(());
+            }
+            listed.iter().for_each(Shortcut::pretty_print);
 
-            println!();
-            println!(
+            outln!();
+            outln!(
                 "Total primary shortcut number: {}",
-                Cyan.paint(shortcuts.len().to_string())
+                style(Cyan).paint(shortcuts.len().to_string())
             );
-            println!(
+            outln!(
                 "Total primary shortcut open times: {}",
-                Cyan.paint(
+                style(Cyan).paint(
                     shortcuts
                         .iter()
                         .fold(0, |acc, shortcut| acc + shortcut.open_times)
@@ -23,51 +31,65 @@ pub fn execute(matches: &ArgMatches) -> anyhow::Result<()> {
                 )
             );
         } else {
-            println!("{}", Red.paint("No primary shortcut found"));
+            eprintln!("{}", style(Red).paint("No primary shortcut found"));
         };
     } else if matches.is_present("secondary") {
         if let Some(domain_shortcut_map) = ShortcutManager::get_secondary_shortcuts() {
+            let listed: Vec<(&String, Vec<Shortcut>)> = domain_shortcut_map
+                .iter()
+                .map(|(domain, shortcuts)| (domain, arrange(matches, shortcuts)))
+                .collect();
+            let all = || listed.iter().flat_map(|(_, shortcuts)| shortcuts);
+            if print_fields(matches, all())? || print_tsv(matches, all())? {
+                return Ok(());
+            }
+            for (domain, shortcuts) in &listed {
+                outln!();
+                outln!("[{}]", style(Cyan.bold()).paint(domain.as_str()));
+                shortcuts.iter().for_each(Shortcut::pretty_print);
+            }
+
             let mut total_number = 0;
             let mut total_open_times = 0;
-            for (domain, shortcuts) in domain_shortcut_map.iter() {
-                println!();
-                println!("[{}]", Cyan.bold().paint(domain));
-
-                shortcuts.iter().for_each(Shortcut::pretty_print);
+            for shortcuts in domain_shortcut_map.values() {
                 total_number += shortcuts.len();
                 total_open_times += shortcuts
                     .iter()
                     .fold(0, |acc, shortcut| acc + shortcut.open_times);
             }
 
-            println!();
-            println!(
+            outln!();
+            outln!(
                 "Total domain number: {}",
-                Cyan.paint(domain_shortcut_map.len().to_string())
+                style(Cyan).paint(domain_shortcut_map.len().to_string())
             );
-            println!(
+            outln!(
                 "Total secondary shortcut number: {}",
-                Cyan.paint(total_number.to_string())
+                style(Cyan).paint(total_number.to_string())
             );
-            println!(
+            outln!(
                 "Total secondary shortcut open times: {}",
-                Cyan.paint(total_open_times.to_string())
+                style(Cyan).paint(total_open_times.to_string())
             );
         } else {
-            println!("{}", Red.paint("No secondary shortcut found."));
+            eprintln!("{}", style(Red).paint("No secondary shortcut found."));
         }
     } else if matches.is_present("compound") {
         if let Some(shortcuts) = ShortcutManager::get_compound_shortcuts() {
-            shortcuts.iter().for_each(Shortcut::pretty_print);
+            let listed = arrange(matches, &shortcuts);
+            if print_fields(matches, &listed)? || print_tsv(matches, &listed)? {
+                return Ok(());
+            }
+            listed.iter().for_each(Shortcut::pretty_print);
 
-            println!();
-            println!(
+            outln!();
+            outln!(
                 "Total compound shortcut number: {}",
-                Cyan.paint(shortcuts.len().to_string())
+                style(Cyan).paint(shortcuts.len().to_string())
             );
-            println!(
+            outln!(
                 "Total compound shortcut open times: {}",
-                Cyan.paint(
+                style(Cyan).paint(
                     shortcuts
                         .iter()
                         .fold(0, |acc, shortcut| acc + shortcut.open_times)
@@ -75,7 +97,7 @@ pub fn execute(matches: &ArgMatches) -> anyhow::Result<()> {
                 )
             );
         } else {
-            println!("{}", Red.paint("No compound shortcut found."));
+            eprintln!("{}", style(Red).paint("No compound shortcut found."));
         }
     } else {
         println!("{}", matches.usage());
@@ -84,3 +106,22 @@ pub fn execute(matches: &ArgMatches) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Apply `--sort` and `--limit` to the **shortcuts**, the totals are still
+/// about all of them.
+fn arrange(matches: &ArgMatches, shortcuts: &[Shortcut]) -> Vec<Shortcut> {
+    let mut shortcuts = shortcuts.to_vec();
+    if let Some(field) = matches
+        .value_of("sort")
+        .and_then(|field| field.parse().ok())
+    {
+        sort::sort(&mut shortcuts, field, matches.is_present("reverse"));
+    }
+    if let Some(limit) = matches
+        .value_of("limit")
+        .and_then(|limit| limit.parse().ok())
+    {
+        shortcuts.truncate(limit);
+    }
+    shortcuts
+}
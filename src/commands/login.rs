@@ -1,6 +1,7 @@
 use clap::ArgMatches;
 
 use crate::api::Api;
+use crate::config::Config;
 use crate::models::Meta;
 use crate::store::Storage;
 use crate::utils::ui;
@@ -8,7 +9,8 @@ use anyhow::Result;
 
 pub fn execute(matches: &ArgMatches) -> Result<()> {
     if let Some(access_token) = matches.value_of("token") {
-        return handle_login(access_token);
+        check_token(access_token)?;
+        return store_token(access_token);
     }
 
     println!("This helps you signing in your anyshortcut-cli with an authentication token.");
@@ -23,23 +25,60 @@ pub fn execute(matches: &ArgMatches) -> Result<()> {
         }
     }
 
-    while let Err(error) = handle_login(&ui::prompt("Enter your token:")?) {
-        println!("{}", error);
+    loop {
+        let access_token = ui::prompt("Enter your token:")?;
+        match check_token(&access_token) {
+            Ok(()) => return store_token(&access_token),
+            Err(error) => println!("{}", error),
+        }
     }
+}
 
+fn check_token(access_token: &str) -> Result<()> {
+    Api::get_current()?.login_with_access_token(access_token)?;
+    println!("Valid access token.");
     Ok(())
 }
 
-fn handle_login(access_token: &str) -> Result<()> {
-    let api = Api::get_current();
-    api.login_with_access_token(&access_token).map(|_| {
-        println!("Valid access token.");
-        Meta {
-            token: access_token.to_string(),
-        }
-        .persist()
-        .unwrap_or_else(|error| println!("{}", error));
+/// Store the valid **access_token** and sync the shortcuts of its user.
+fn store_token(access_token: &str) -> Result<()> {
+    if Config::current().dry_run {
+        let path = Meta::get_file_path()
+            .map(|path| path.display().to_string())
+            .unwrap_or_default();
+        println!(
+            "Dry run, login would store the token {} in {} and sync your shortcuts.",
+            mask_token(access_token),
+            path
+        );
+        return Ok(());
+    }
+    Meta {
+        token: access_token.to_string(),
+    }
+    .persist()?;
 
-        super::sync_all_shortcuts();
-    })
+    super::sync_all_shortcuts(false, None)
+}
+
+/// Returns the first chars of the **token**, enough to tell which one it is.
+fn mask_token(token: &str) -> String {
+    let shown: String = token.chars().take(4).collect();
+    let hidden = token.chars().count().saturating_sub(4);
+    format!("{}{}", shown, "*".repeat(hidden))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mask_token() {
+        assert_eq!(mask_token("abcdef123"), "abcd*****");
+        assert_eq!(mask_token("abc"), "abc");
+        assert_eq!(
+            mask_token("\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}"),
+            "\u{e9}\u{e9}\u{e9}\u{e9}*"
+        );
+    }
 }
@@ -1,9 +1,40 @@
+use std::io::{self, IsTerminal};
+
+use anyhow::bail;
 use clap::ArgMatches;
 
+use crate::config::Config;
 use crate::models::*;
 use crate::store::Storage;
+use crate::utils::ui;
+
+pub fn execute(matches: &ArgMatches) -> anyhow::Result<()> {
+    if Config::current().dry_run {
+        println!("Dry run, logout would remove:");
+        for path in &[
+            Meta::get_file_path()?,
+            PrimaryShortcutVec::get_file_path()?,
+            SecondaryShortcutMap::get_file_path()?,
+        ] {
+            if path.exists() {
+                println!("  {}", path.display());
+            }
+        }
+        return Ok(());
+    }
+
+    let count = local_shortcut_count();
+    if count > 1 && !matches.is_present("yes") {
+        let message = format!("Logout deletes your {} local shortcuts.", count);
+        if !io::stdin().is_terminal() {
+            bail!("{} Pass --yes to confirm.", message);
+        }
+        if !ui::prompt_for_yes(&message)? {
+            println!("Logout cancelled.");
+            return Ok(());
+        }
+    }
 
-pub fn execute(_: &ArgMatches) -> anyhow::Result<()> {
     Meta::clear()?;
     PrimaryShortcutVec::clear()?;
     SecondaryShortcutMap::clear()?;
@@ -11,3 +42,11 @@ pub fn execute(_: &ArgMatches) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+fn local_shortcut_count() -> usize {
+    let primary = PrimaryShortcutVec::parse().map_or(0, |shortcuts| shortcuts.len());
+    let secondary = SecondaryShortcutMap::parse().map_or(0, |shortcuts| {
+        shortcuts.values().map(|shortcuts| shortcuts.len()).sum()
+    });
+    primary + secondary
+}
@@ -1,53 +1,145 @@
 use ansi_term::Color::{Cyan, Green};
+use anyhow::{bail, Result};
+use clap::ArgMatches;
 
 use crate::api::Api;
+use crate::models::{Shortcut, ShortcutData, ShortcutManager};
 use crate::store::{self, Storage};
+use crate::utils::color::style;
+use crate::utils::diff::{self, DiffFormat};
+use crate::utils::field;
+use crate::utils::output::outln;
 
+pub mod doctor;
+pub mod export;
+pub mod get;
 pub mod list;
 pub mod login;
 pub mod logout;
+pub mod profile;
+pub mod raw;
+pub mod search;
 pub mod sync;
+pub mod version;
 
-/// Sync all shortcuts
-pub fn sync_all_shortcuts() {
-    println!();
-    println!("Syncing your shortcut data...");
-    match Api::get_current().get_all_shortcuts() {
-        Ok(response) => {
-            response
-                .primary
-                .persist()
-                .unwrap_or_else(|error| println!("{}", error));
-            response
-                .secondary
-                .persist()
-                .unwrap_or_else(|error| println!("{}", error));
+/// Sync all shortcuts, a **dry_run** only shows what would be stored.
+///
+/// With a **diff** format the changes to the local shortcuts are printed first,
+/// a JSON diff is the only output then, so tools can parse it.
+pub fn sync_all_shortcuts(dry_run: bool, diff: Option<DiffFormat>) -> Result<()> {
+    let quiet = diff == Some(DiffFormat::Json);
+    if !quiet {
+        println!();
+        println!("Syncing your shortcut data...");
+    }
+    let response = Api::get_current()?.get_all_shortcuts()?;
 
+    if let Some(format) = diff {
+        let local = ShortcutManager::get_all_shortcuts();
+        if !quiet {
             println!();
-            println!("{}", Green.paint("Shortcuts synced success!"));
-            println!(
-                "Primary shortcut number: {}",
-                Cyan.paint(response.primary.len().to_string())
-            );
-            println!(
-                "Secondary shortcut number: {}",
-                Cyan.paint(
-                    response
-                        .secondary
-                        .values()
-                        .fold(0, |acc, shortcuts| acc + shortcuts.len())
-                        .to_string()
-                )
-            );
+        }
+        diff::print(&diff::diff(&local, response.shortcuts()), format)?;
+    }
+    if dry_run {
+        if diff.is_none() {
+            println!();
+            println!("Dry run, sync would replace the local shortcuts with:");
+            print_shortcut_numbers(&response);
             println!();
+        }
+        return Ok(());
+    }
+
+    response.primary.persist()?;
+    response.secondary.persist()?;
+    if quiet {
+        return Ok(());
+    }
 
-            // Get the store directory PathBuf object.
-            let dir = store::get_store_directory().unwrap();
-            println!(
-                "All your data stored at {} directory.",
-                Cyan.paint(format!("{}", dir.display()))
-            );
+    println!();
+    println!("{}", style(Green).paint("Shortcuts synced success!"));
+    print_shortcut_numbers(&response);
+    println!();
+
+    // Get the store directory PathBuf object.
+    let dir = store::get_store_directory()?;
+    println!(
+        "All your data stored at {} directory.",
+        style(Cyan).paint(format!("{}", dir.display()))
+    );
+    Ok(())
+}
+
+/// Print the `--field` of every shortcut one per line, returns `false` without `--field`.
+pub fn print_fields<'a>(
+    matches: &ArgMatches,
+    shortcuts: impl IntoIterator<Item = &'a Shortcut>,
+) -> Result<bool> {
+    let path = match matches.value_of("field") {
+        Some(path) => path,
+        None => return Ok(false),
+    };
+    for shortcut in shortcuts {
+        let value = serde_json::to_value(shortcut)?;
+        match field::select(&value, path) {
+            Some(value) => outln!("{}", field::to_plain(value)),
+            None if matches.is_present("require_field") => {
+                bail!(
+                    "Shortcut {} has no field {}.",
+                    shortcut.key.to_uppercase(),
+                    path
+                )
+            }
+            None => outln!(),
         }
-        Err(error) => println!("{}", error),
     }
+    Ok(true)
+}
+
+/// Print the shortcuts as tab-separated `--columns` with `--format tsv`,
+/// returns `false` for another format.
+pub fn print_tsv<'a>(
+    matches: &ArgMatches,
+    shortcuts: impl IntoIterator<Item = &'a Shortcut>,
+) -> Result<bool> {
+    if matches.value_of("format") != Some("tsv") {
+        return Ok(false);
+    }
+    let columns: Vec<&str> = matches
+        .value_of("columns")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .collect();
+    if !matches.is_present("no_header") {
+        outln!("{}", columns.join("\t"));
+    }
+    for shortcut in shortcuts {
+        let value = serde_json::to_value(shortcut)?;
+        let cells: Vec<String> = columns
+            .iter()
+            .map(|column| {
+                field::select(&value, column).map_or_else(String::new, field::to_tsv_cell)
+            })
+            .collect();
+        outln!("{}", cells.join("\t"));
+    }
+    Ok(true)
+}
+
+fn print_shortcut_numbers(data: &ShortcutData) {
+    println!(
+        "Primary shortcut number: {}",
+        style(Cyan).paint(data.primary.len().to_string())
+    );
+    println!(
+        "Secondary shortcut number: {}",
+        style(Cyan).paint(
+            data.secondary
+                .values()
+                .fold(0, |acc, shortcuts| acc + shortcuts.len())
+                .to_string()
+        )
+    );
 }
@@ -0,0 +1,77 @@
+use ansi_term::Color::{Cyan, Green};
+use anyhow::{bail, Result};
+use clap::ArgMatches;
+
+use crate::profile::{Profile, Profiles, DEFAULT_PROFILE};
+use crate::utils::color::style;
+use crate::utils::output::outln;
+
+pub fn execute(matches: &ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        ("list", Some(_)) => list(),
+        ("use", Some(use_matches)) => use_profile(use_matches.value_of("name").unwrap()),
+        ("add", Some(add_matches)) => add(
+            add_matches.value_of("name").unwrap(),
+            add_matches.value_of("base_url"),
+        ),
+        _ => {
+            println!("{}", matches.usage());
+            println!("For detail usage, please run with -h or --help.");
+            Ok(())
+        }
+    }
+}
+
+fn list() -> Result<()> {
+    let profiles = Profiles::load()?;
+    let active = Profiles::active_name();
+    print_profile(DEFAULT_PROFILE, &Profile::default(), &active);
+    for (name, profile) in &profiles.profiles {
+        print_profile(name, profile, &active);
+    }
+    Ok(())
+}
+
+fn print_profile(name: &str, profile: &Profile, active: &str) {
+    outln!(
+        "{} {}  {}",
+        if name == active { "*" } else { " " },
+        style(Cyan).paint(name),
+        profile.api_url.as_deref().unwrap_or("")
+    );
+}
+
+fn use_profile(name: &str) -> Result<()> {
+    let mut profiles = Profiles::load()?;
+    if !profiles.contains(name) {
+        bail!(
+            "Unknown profile {}, add it with `profile add {}` first.",
+            name,
+            name
+        );
+    }
+    profiles.active = Some(name.to_string());
+    profiles.persist()?;
+    println!("Using profile {} now.", style(Cyan).paint(name));
+    Ok(())
+}
+
+fn add(name: &str, base_url: Option<&str>) -> Result<()> {
+    let mut profiles = Profiles::load()?;
+    if profiles.contains(name) {
+        bail!("Profile {} already exists.", name);
+    }
+    profiles.profiles.insert(
+        name.to_string(),
+        Profile {
+            api_url: base_url.map(String::from),
+        },
+    );
+    profiles.persist()?;
+    println!("{}", style(Green).paint(format!("Profile {} added.", name)));
+    println!(
+        "Run `anyshortcut --profile {} login` to sign in with its token.",
+        name
+    );
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use std::io::{self, IsTerminal};
+
+use anyhow::{bail, Result};
+use clap::ArgMatches;
+use curl_http::Method;
+
+use crate::api::{Api, RawBody};
+use crate::utils::output::{self, outln};
+
+pub fn execute(matches: &ArgMatches) -> Result<()> {
+    let method: Method = matches.value_of("method").unwrap_or("GET").parse()?;
+    let path = matches.value_of("path").unwrap_or("/");
+    let endpoint = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("/{}", path)
+    };
+    let headers: Vec<(&str, &str)> = matches
+        .values_of("header")
+        .into_iter()
+        .flatten()
+        .filter_map(|header| header.split_once(':'))
+        .map(|(name, value)| (name.trim(), value.trim()))
+        .collect();
+    let body = match matches.value_of("data") {
+        Some("-") => {
+            if io::stdin().is_terminal() {
+                bail!("--data - reads the body from stdin, pipe it in e.g. with `< body.json`.");
+            }
+            Some(RawBody::Reader(Box::new(io::stdin())))
+        }
+        Some(data) => Some(RawBody::Data(data.as_bytes().to_vec())),
+        None => None,
+    };
+
+    let response = Api::get_current()?.raw(method, &endpoint, &headers, body)?;
+    if matches.is_present("include") {
+        outln!("{}", response.status_line().unwrap_or_default());
+        for (name, value) in response.header_pairs() {
+            outln!("{}: {}", name, value);
+        }
+        outln!();
+    }
+    let body = String::from_utf8_lossy(response.body());
+    if body.is_empty() || body.ends_with('\n') {
+        output::print(format_args!("{}", body));
+    } else {
+        outln!("{}", body);
+    }
+
+    if matches.is_present("fail") && response.failed() {
+        bail!("The server answered with status {}.", response.status());
+    }
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use ansi_term::Color::{Red, Yellow};
+use ansi_term::{ANSIString, ANSIStrings, Style};
+use anyhow::{bail, Result};
+use clap::ArgMatches;
+
+use crate::api::Api;
+use crate::commands::print_fields;
+use crate::models::{Meta, Shortcut, ShortcutManager};
+use crate::utils::color::style;
+use crate::utils::fuzzy::{fuzzy_match, FuzzyMatch};
+use crate::utils::output::outln;
+
+/// A shortcut matching the query, with the field which matched best.
+struct SearchResult<'a> {
+    shortcut: &'a Shortcut,
+    field: Field,
+    matched: FuzzyMatch,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Field {
+    Title,
+    Url,
+    Domain,
+    Comment,
+}
+
+pub fn execute(matches: &ArgMatches) -> Result<()> {
+    let query = matches.value_of("query").unwrap_or_default();
+    let shortcuts = if matches.is_present("remote") {
+        if Meta::resolve_token()?.is_none() {
+            bail!("Can't search remotely, you are not in login state. Please run login first.");
+        }
+        Api::get_current()?.get_all_shortcuts()?.into_shortcuts()
+    } else {
+        ShortcutManager::get_all_shortcuts()
+    };
+
+    let results = search(query, &shortcuts);
+    if results.is_empty() {
+        eprintln!("{}", style(Red).paint("No shortcut found."));
+        return Ok(());
+    }
+    if matches.is_present("open_first") {
+        ShortcutManager::open_shortcut(results[0].shortcut);
+        return Ok(());
+    }
+    if print_fields(matches, results.iter().map(|result| result.shortcut))? {
+        return Ok(());
+    }
+    for result in &results {
+        print_result(result);
+    }
+
+    Ok(())
+}
+
+/// Returns the shortcuts matching the **query**, the best match first.
+fn search<'a>(query: &str, shortcuts: &'a [Shortcut]) -> Vec<SearchResult<'a>> {
+    let mut results: Vec<SearchResult> = shortcuts
+        .iter()
+        .filter_map(|shortcut| {
+            [Field::Title, Field::Url, Field::Domain, Field::Comment]
+                .iter()
+                .filter_map(|&field| {
+                    let matched = fuzzy_match(query, field_text(shortcut, field))?;
+                    Some(SearchResult {
+                        shortcut,
+                        field,
+                        matched,
+                    })
+                })
+                .max_by_key(|result| result.matched.score)
+        })
+        .collect();
+    results.sort_by(|a, b| {
+        b.matched
+            .score
+            .cmp(&a.matched.score)
+            .then(b.shortcut.open_times.cmp(&a.shortcut.open_times))
+    });
+    results
+}
+
+fn field_text(shortcut: &Shortcut, field: Field) -> &str {
+    match field {
+        Field::Title => &shortcut.title,
+        Field::Url => &shortcut.url,
+        Field::Domain => &shortcut.domain,
+        Field::Comment => shortcut.comment.as_deref().unwrap_or(""),
+    }
+}
+
+fn print_result(result: &SearchResult) {
+    let shortcut = result.shortcut;
+    let paint = |field: Field, plain: Style| {
+        let text = field_text(shortcut, field);
+        if field == result.field {
+            highlight(text, &result.matched.positions, plain)
+        } else {
+            vec![style(plain).paint(text.to_string())]
+        }
+    };
+    outln!(
+        "{}  {}  {}",
+        style(Yellow.bold()).paint(format!("[{}]", shortcut.key.to_uppercase())),
+        ANSIStrings(&paint(Field::Title, Style::new().bold())),
+        ANSIStrings(&paint(Field::Url, Style::new().dimmed())),
+    );
+    if result.field == Field::Domain || result.field == Field::Comment {
+        outln!("     {}", ANSIStrings(&paint(result.field, Style::new())));
+    }
+}
+
+/// Paint the chars at **positions** highlighted and the rest with the **plain** style.
+fn highlight(text: &str, positions: &[usize], plain: Style) -> Vec<ANSIString<'static>> {
+    text.chars()
+        .enumerate()
+        .map(|(index, c)| {
+            if positions.contains(&index) {
+                style(Yellow.underline()).paint(c.to_string())
+            } else {
+                style(plain).paint(c.to_string())
+            }
+        })
+        .collect()
+}
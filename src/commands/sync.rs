@@ -1,15 +1,27 @@
 use ansi_term::Color::Red;
 use clap::ArgMatches;
 
+use crate::config::Config;
 use crate::models::Meta;
+use crate::utils::color::style;
+use crate::utils::diff::DiffFormat;
 
-pub fn execute(_: &ArgMatches) -> anyhow::Result<()> {
-    if Meta::has_token() {
-        super::sync_all_shortcuts();
+pub fn execute(matches: &ArgMatches) -> anyhow::Result<()> {
+    if Meta::resolve_token()?.is_some() {
+        let diff_only = matches.is_present("diff_only");
+        let diff = if matches.is_present("json") {
+            Some(DiffFormat::Json)
+        } else if diff_only || matches.is_present("diff") {
+            Some(DiffFormat::Text)
+        } else {
+            None
+        };
+        super::sync_all_shortcuts(Config::current().dry_run || diff_only, diff)?;
     } else {
         println!(
             "{}",
-            Red.paint("Can't sync data, you are not in login state. Please run login first.")
+            style(Red)
+                .paint("Can't sync data, you are not in login state. Please run login first.")
         );
     }
 
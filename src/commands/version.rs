@@ -0,0 +1,35 @@
+use ansi_term::Color::Cyan;
+use anyhow::Result;
+use clap::{crate_name, crate_version, ArgMatches};
+use serde_derive::Serialize;
+
+use crate::utils::color::style;
+use crate::utils::output::outln;
+
+#[derive(Serialize)]
+struct VersionInfo {
+    version: &'static str,
+    curl_version: String,
+    ssl_version: Option<String>,
+    curl_features: Vec<&'static str>,
+}
+
+pub fn execute(matches: &ArgMatches) -> Result<()> {
+    let curl = curl_http::curl_info();
+    let info = VersionInfo {
+        version: crate_version!(),
+        curl_version: curl.version,
+        ssl_version: curl.ssl_version,
+        curl_features: curl.features,
+    };
+
+    if matches.is_present("json") {
+        outln!("{}", serde_json::to_string_pretty(&info)?);
+        return Ok(());
+    }
+    outln!("{} {}", crate_name!(), style(Cyan).paint(info.version));
+    outln!("libcurl {}", info.curl_version);
+    outln!("TLS {}", info.ssl_version.as_deref().unwrap_or("none"));
+    outln!("Features {}", info.curl_features.join(" "));
+    Ok(())
+}
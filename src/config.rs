@@ -0,0 +1,83 @@
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Duration;
+
+use clap::ArgMatches;
+
+use crate::cli::parse_duration;
+use crate::utils::color::ColorChoice;
+
+thread_local! {
+    static CONFIG: RefCell<Rc<Config>> = RefCell::new(Rc::new(Config::default()));
+}
+
+/// Global options which apply to every command.
+#[derive(Default, Debug)]
+pub struct Config {
+    /// Use this API server instead of the official one.
+    pub api_url: Option<String>,
+    /// Use this profile instead of the one selected with `profile use`.
+    pub profile: Option<String>,
+    /// Allow an API url without https.
+    pub insecure_http: bool,
+    /// When to colorize the output.
+    pub color: ColorChoice,
+    /// Record all HTTP requests into this HAR file.
+    pub har_path: Option<PathBuf>,
+    /// Resolve host names through this DNS-over-HTTPS server.
+    pub doh_url: Option<String>,
+    /// Read the token from this file instead of using the one of the login.
+    pub token_file: Option<PathBuf>,
+    /// Never send any request.
+    pub offline: bool,
+    /// Only show what would be changed.
+    pub dry_run: bool,
+    /// Write the primary output into this file.
+    pub output_file: Option<PathBuf>,
+    /// Overwrite an existing output file.
+    pub force: bool,
+    /// Give up on a request after this long.
+    pub timeout: Option<Duration>,
+    /// How often to retry failed requests.
+    pub retries: u32,
+}
+
+impl Config {
+    /// Build the config from the global command line options.
+    pub fn from_matches(matches: &ArgMatches) -> Config {
+        Config {
+            api_url: matches.value_of("api_url").map(String::from),
+            profile: matches.value_of("profile").map(String::from),
+            insecure_http: matches.is_present("insecure_http"),
+            color: matches
+                .value_of("color")
+                .and_then(|color| color.parse().ok())
+                .unwrap_or_default(),
+            har_path: matches.value_of("har").map(PathBuf::from),
+            doh_url: matches.value_of("doh").map(String::from),
+            token_file: matches.value_of("token_file").map(PathBuf::from),
+            offline: matches.is_present("offline"),
+            dry_run: matches.is_present("dry_run"),
+            output_file: matches.value_of("output_file").map(PathBuf::from),
+            force: matches.is_present("force"),
+            timeout: matches
+                .value_of("timeout")
+                .and_then(|timeout| parse_duration(timeout).ok()),
+            retries: matches
+                .value_of("retries")
+                .and_then(|retries| retries.parse().ok())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Make this config the current one for the thread.
+    pub fn bind_to_process(self) {
+        CONFIG.with(|config| *config.borrow_mut() = Rc::new(self));
+    }
+
+    /// Returns the current config for the thread.
+    pub fn current() -> Rc<Config> {
+        CONFIG.with(|config| config.borrow().clone())
+    }
+}
@@ -0,0 +1,285 @@
+use std::cell::RefCell;
+use std::path::PathBuf;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::{crate_name, crate_version};
+use serde_derive::Serialize;
+
+use curl_http::{create_private_file, redact, Exchange, Observer};
+
+/// Records every HTTP exchange into a HAR (HTTP Archive) file.
+///
+/// The file is rewritten after each response so it stays complete
+/// even if the command fails halfway. Credentials in urls, headers and
+/// JSON bodies are redacted, and only the user may read the file.
+pub struct HarRecorder {
+    path: PathBuf,
+    entries: RefCell<Vec<Entry>>,
+}
+
+#[derive(Serialize)]
+struct Har<'a> {
+    log: Log<'a>,
+}
+
+#[derive(Serialize)]
+struct Log<'a> {
+    version: &'static str,
+    creator: Creator,
+    entries: &'a [Entry],
+}
+
+#[derive(Serialize)]
+struct Creator {
+    name: &'static str,
+    version: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    started_date_time: String,
+    time: f64,
+    request: HarRequest,
+    response: HarResponse,
+    cache: Cache,
+    timings: Timings,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HarRequest {
+    method: String,
+    url: String,
+    http_version: String,
+    cookies: Vec<NameValue>,
+    headers: Vec<NameValue>,
+    query_string: Vec<NameValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    post_data: Option<PostData>,
+    headers_size: i64,
+    body_size: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HarResponse {
+    status: u32,
+    status_text: String,
+    http_version: String,
+    cookies: Vec<NameValue>,
+    headers: Vec<NameValue>,
+    content: Content,
+    #[serde(rename = "redirectURL")]
+    redirect_url: String,
+    headers_size: i64,
+    body_size: i64,
+}
+
+#[derive(Serialize)]
+struct NameValue {
+    name: String,
+    value: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PostData {
+    mime_type: String,
+    text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Content {
+    size: i64,
+    mime_type: String,
+    text: String,
+}
+
+#[derive(Serialize)]
+struct Cache {}
+
+#[derive(Serialize)]
+struct Timings {
+    send: f64,
+    wait: f64,
+    receive: f64,
+}
+
+impl HarRecorder {
+    pub fn new(path: PathBuf) -> HarRecorder {
+        HarRecorder {
+            path,
+            entries: RefCell::new(vec![]),
+        }
+    }
+
+    fn persist(&self) -> anyhow::Result<()> {
+        let entries = self.entries.borrow();
+        let har = Har {
+            log: Log {
+                version: "1.2",
+                creator: Creator {
+                    name: crate_name!(),
+                    version: crate_version!(),
+                },
+                entries: &entries,
+            },
+        };
+        let file = create_private_file(&self.path)?;
+        serde_json::to_writer_pretty(file, &har)?;
+        Ok(())
+    }
+}
+
+impl Observer for HarRecorder {
+    fn on_response(&self, exchange: &Exchange) {
+        self.entries.borrow_mut().push(Entry::from(exchange));
+        if let Err(error) = self.persist() {
+            eprintln!(
+                "Could not write HAR file {}: {}",
+                self.path.display(),
+                error
+            );
+        }
+    }
+}
+
+impl<'a> From<&Exchange<'a>> for Entry {
+    fn from(exchange: &Exchange<'a>) -> Entry {
+        let response = exchange.response;
+        let status_line = response.status_line().unwrap_or("");
+        let mut status_parts = status_line.splitn(3, ' ');
+        let http_version = status_parts.next().unwrap_or("").to_string();
+        let status_text = status_parts.nth(1).unwrap_or("").to_string();
+
+        let request_headers = exchange
+            .request_headers
+            .iter()
+            .map(|(name, value)| redact_header(name, value))
+            .collect::<Vec<_>>();
+        let response_headers: Vec<NameValue> = response
+            .header_pairs()
+            .map(|(name, value)| redact_header(name, value))
+            .collect();
+        let content_type = |headers: &[NameValue]| {
+            headers
+                .iter()
+                .find(|header| header.name.eq_ignore_ascii_case("Content-Type"))
+                .map(|header| header.value.clone())
+                .unwrap_or_default()
+        };
+
+        let post_data = exchange.request_body.map(|body| PostData {
+            mime_type: content_type(&request_headers),
+            text: redact::body(body),
+        });
+        let body = response.body();
+        let elapsed = exchange.elapsed.as_secs_f64() * 1000.0;
+
+        Entry {
+            started_date_time: DateTime::<Utc>::from(exchange.started_at)
+                .to_rfc3339_opts(SecondsFormat::Millis, true),
+            time: elapsed,
+            request: HarRequest {
+                method: exchange.method.to_string(),
+                url: redact::url(exchange.url),
+                http_version: http_version.clone(),
+                cookies: vec![],
+                query_string: query_string(exchange.url),
+                headers: request_headers,
+                post_data,
+                headers_size: -1,
+                body_size: exchange.request_body.map_or(0, |body| body.len() as i64),
+            },
+            response: HarResponse {
+                status: response.status(),
+                status_text,
+                http_version,
+                cookies: vec![],
+                content: Content {
+                    size: body.len() as i64,
+                    mime_type: content_type(&response_headers),
+                    text: redact::body(body),
+                },
+                headers: response_headers,
+                redirect_url: String::new(),
+                headers_size: -1,
+                body_size: body.len() as i64,
+            },
+            cache: Cache {},
+            timings: Timings {
+                send: 0.0,
+                wait: elapsed,
+                receive: 0.0,
+            },
+        }
+    }
+}
+
+fn redact_header(name: &str, value: &str) -> NameValue {
+    NameValue {
+        name: name.to_string(),
+        value: redact::header(name, value).to_string(),
+    }
+}
+
+/// The query of the **url**, which holds the access token, with its values redacted.
+fn query_string(url: &str) -> Vec<NameValue> {
+    redact::url(url)
+        .split_once('?')
+        .map(|(_, query)| query.split('#').next().unwrap_or(""))
+        .unwrap_or("")
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            NameValue {
+                name: name.to_string(),
+                value: value.to_string(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_string_is_redacted() {
+        let url = "https://api.anyshortcut.com/shortcuts/all?nested=false&access_token=s3cret";
+        let query = serde_json::to_string(&query_string(url)).unwrap();
+        assert!(!query.contains("s3cret"));
+        assert!(query.contains(r#"{"name":"access_token","value":"[REDACTED]"}"#));
+        assert!(query_string("https://a.com/user/login").is_empty());
+    }
+
+    #[test]
+    fn test_har_file_is_private() {
+        let path = std::env::temp_dir().join(format!("anyshortcut-{}.har", std::process::id()));
+        HarRecorder::new(path.clone()).persist().unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_redact_header() {
+        for name in &[
+            "Authorization",
+            "Proxy-Authorization",
+            "cookie",
+            "Set-Cookie",
+        ] {
+            let header = serde_json::to_string(&redact_header(name, "s3cret")).unwrap();
+            assert!(!header.contains("s3cret"), "{}", header);
+        }
+        assert_eq!(redact_header("Accept", "*/*").value, "*/*");
+    }
+}
@@ -1,35 +1,66 @@
 use std::process;
 
+use ansi_term::Color::Red;
 use clap::ArgMatches;
 
-use crate::commands::{list, login, logout, sync};
+use crate::commands::{doctor, export, get, list, login, logout, raw, search, sync, version};
+use crate::config::Config;
 use crate::models::ShortcutManager;
+use crate::profile::Profiles;
+use crate::utils::color::style;
+use crate::utils::output;
 
 mod api;
 mod cli;
 mod commands;
+mod config;
+mod har;
 mod models;
+mod profile;
 mod store;
 mod utils;
 
 fn main() {
     let matches = cli::build_cli().get_matches();
+    Config::from_matches(&matches).bind_to_process();
 
-    match handle_matches(&matches) {
+    match output::begin()
+        .and_then(|_| handle_matches(&matches))
+        .and_then(|_| output::finish())
+    {
         Ok(()) => process::exit(0),
         Err(error) => {
-            println!("Error: {}", error);
+            output::discard();
+            eprintln!("{} {}", style(Red).paint("Error:"), error);
             process::exit(1);
         }
     };
 }
 
 fn handle_matches(matches: &ArgMatches) -> anyhow::Result<()> {
+    let uses_profile = match matches.subcommand_name() {
+        Some("profile" | "version") => false,
+        Some(_) => true,
+        // Without a key only the help is printed.
+        None => matches.is_present("primary_key"),
+    };
+    if uses_profile {
+        // Fail early instead of working on an empty storage of a mistyped profile.
+        Profiles::active()?;
+    }
+
     match matches.subcommand() {
-        ("login", Some(login_matches)) => login::execute(&login_matches)?,
-        ("logout", Some(logout_matches)) => logout::execute(&logout_matches)?,
-        ("sync", Some(sync_matches)) => sync::execute(&sync_matches)?,
-        ("list", Some(list_matches)) => list::execute(&list_matches)?,
+        ("login", Some(login_matches)) => login::execute(login_matches)?,
+        ("logout", Some(logout_matches)) => logout::execute(logout_matches)?,
+        ("sync", Some(sync_matches)) => sync::execute(sync_matches)?,
+        ("list", Some(list_matches)) => list::execute(list_matches)?,
+        ("get", Some(get_matches)) => get::execute(get_matches)?,
+        ("search", Some(search_matches)) => search::execute(search_matches)?,
+        ("doctor", Some(doctor_matches)) => doctor::execute(doctor_matches)?,
+        ("export", Some(export_matches)) => export::execute(export_matches)?,
+        ("raw", Some(raw_matches)) => raw::execute(raw_matches)?,
+        ("version", Some(version_matches)) => version::execute(version_matches)?,
+        ("profile", Some(profile_matches)) => commands::profile::execute(profile_matches)?,
         _ => {
             match (
                 matches.value_of("primary_key"),
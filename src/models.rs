@@ -1,13 +1,22 @@
 use ansi_term::Color::Yellow;
 use ansi_term::{ANSIString, ANSIStrings, Style};
+use anyhow::{anyhow, bail, Result};
 use chrono::{TimeZone, Utc};
 use serde_derive::{Deserialize, Serialize};
+use std::cell::OnceCell;
 use std::collections::HashMap;
 use std::fmt;
+use std::fs;
+use std::hash::{Hash, Hasher};
 use std::ops::Deref;
+use std::path::Path;
 use storage_derive::Storage;
 
+use crate::config::Config;
 use crate::store::Storage;
+use crate::utils::color::style;
+use crate::utils::diff;
+use crate::utils::output::outln;
 
 #[derive(Storage, Serialize, Deserialize, Debug)]
 #[store_at = "meta.json"]
@@ -15,9 +24,19 @@ pub struct Meta {
     pub token: String,
 }
 
+thread_local! {
+    /// The token of the `--token-file`, which is read only once.
+    static FILE_TOKEN: OnceCell<Result<String, String>> = const { OnceCell::new() };
+}
+
 ///
 /// The Shortcut struct.
 ///
+/// Shortcuts are equal and hash alike when they have the same `id`, the server
+/// keeps it when the key, url or anything else changes. The key is no identity,
+/// secondary shortcuts of different domains share keys. Use
+/// [`Shortcut::same_content`] to compare all fields.
+///
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Shortcut {
     pub id: u32,
@@ -31,35 +50,61 @@ pub struct Shortcut {
     pub timestamp: i64,
 }
 
+impl PartialEq for Shortcut {
+    fn eq(&self, other: &Shortcut) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Shortcut {}
+
+impl Hash for Shortcut {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
 impl Shortcut {
+    /// Whether all fields are equal, not only the id.
+    pub fn same_content(&self, other: &Shortcut) -> bool {
+        diff::diff_fields(self, other).is_empty()
+    }
+
     pub fn pretty_print(&self) {
-        println!();
-        println!("{}", "-".repeat(60));
+        outln!();
+        outln!("{}", "-".repeat(60));
         let key_str: &[ANSIString<'static>] = &[
-            Yellow.paint("["),
-            Yellow.bold().paint(self.key.to_uppercase()),
-            Yellow.paint("]"),
+            style(Yellow).paint("["),
+            style(Yellow.bold()).paint(self.key.to_uppercase()),
+            style(Yellow).paint("]"),
         ];
-        println!(
+        outln!(
             "{}  {}",
             ANSIStrings(key_str),
-            Style::new().bold().paint(&self.title)
+            style(Style::new().bold()).paint(&self.title)
         );
 
-        println!();
+        outln!();
         self.fixed_label_print("Url:", &self.url);
         self.fixed_label_print(
             "Comment:",
             self.comment.as_ref().unwrap_or(&String::from("")),
         );
         self.fixed_label_print("Domain:", &self.domain);
-        self.fixed_label_print("Open times:", &self.open_times);
-        self.fixed_label_print("Created at:", Utc.timestamp_millis(self.timestamp));
-        println!();
+        self.fixed_label_print("Open times:", self.open_times);
+        self.fixed_label_print(
+            "Created at:",
+            Utc.timestamp_millis_opt(self.timestamp).unwrap(),
+        );
+        outln!();
     }
 
     fn fixed_label_print(&self, label: &str, text: impl fmt::Display) {
-        println!("{:14}{}", label, text);
+        outln!(
+            "{}{}",
+            style(Style::new().dimmed()).paint(format!("{:14}", label)),
+            text
+        );
     }
 }
 
@@ -82,6 +127,37 @@ pub struct ShortcutData {
     pub secondary: SecondaryShortcutMap,
 }
 
+impl ShortcutData {
+    /// Flatten the primary and secondary shortcuts into one list.
+    pub fn into_shortcuts(self) -> Vec<Shortcut> {
+        let mut shortcuts = self.primary.0;
+        shortcuts.extend(self.secondary.0.into_values().flatten());
+        shortcuts
+    }
+
+    /// Returns the shortcut of the **primary_key**, or with a **secondary_key** the
+    /// secondary shortcut of the domain of the primary one.
+    pub fn find(&self, primary_key: &str, secondary_key: Option<&str>) -> Option<&Shortcut> {
+        let primary = self
+            .primary
+            .iter()
+            .find(|shortcut| shortcut.key.eq_ignore_ascii_case(primary_key))?;
+        match secondary_key {
+            Some(key) => self
+                .secondary
+                .get(&primary.domain)?
+                .iter()
+                .find(|shortcut| shortcut.key.eq_ignore_ascii_case(key)),
+            None => Some(primary),
+        }
+    }
+
+    /// Iterate over the primary and secondary shortcuts.
+    pub fn shortcuts(&self) -> impl Iterator<Item = &Shortcut> {
+        self.primary.iter().chain(self.secondary.values().flatten())
+    }
+}
+
 pub struct ShortcutManager {}
 
 impl ShortcutManager {
@@ -89,8 +165,8 @@ impl ShortcutManager {
         PrimaryShortcutVec::parse().ok().map(|shortcuts| {
             shortcuts
                 .iter()
+                .filter(|&shortcut| shortcut.key.len() == 1)
                 .cloned()
-                .filter(|shortcut| shortcut.key.len() == 1)
                 .collect()
         })
     }
@@ -99,8 +175,8 @@ impl ShortcutManager {
         PrimaryShortcutVec::parse().ok().map(|shortcuts| {
             shortcuts
                 .iter()
+                .filter(|&shortcut| shortcut.key.len() == 2)
                 .cloned()
-                .filter(|shortcut| shortcut.key.len() == 2)
                 .collect()
         })
     }
@@ -113,8 +189,8 @@ impl ShortcutManager {
         match PrimaryShortcutVec::parse() {
             Ok(shortcuts) => shortcuts
                 .iter()
-                .cloned()
-                .find(|shortcut| shortcut.key.eq_ignore_ascii_case(key)),
+                .find(|&shortcut| shortcut.key.eq_ignore_ascii_case(key))
+                .cloned(),
             Err(_) => None,
         }
     }
@@ -125,8 +201,8 @@ impl ShortcutManager {
                 if let Some(shortcuts) = domain_shortcut_map.get(domain) {
                     shortcuts
                         .iter()
+                        .find(|&shortcut| shortcut.key.eq_ignore_ascii_case(key))
                         .cloned()
-                        .find(|shortcut| shortcut.key.eq_ignore_ascii_case(key))
                 } else {
                     None
                 }
@@ -135,7 +211,6 @@ impl ShortcutManager {
         }
     }
 
-    #[allow(unused)]
     pub fn get_secondary_by_keys(primary_key: &str, secondary_key: &str) -> Option<Shortcut> {
         if let Some(shortcut) = Self::get_primary_by_key(primary_key) {
             Self::get_secondary_by_domain_key(&shortcut.domain, secondary_key)
@@ -144,6 +219,15 @@ impl ShortcutManager {
         }
     }
 
+    /// Get the primary and secondary shortcuts in one list.
+    pub fn get_all_shortcuts() -> Vec<Shortcut> {
+        let mut shortcuts = PrimaryShortcutVec::parse().map_or(vec![], |shortcuts| shortcuts.0);
+        if let Ok(secondary) = SecondaryShortcutMap::parse() {
+            shortcuts.extend(secondary.0.into_values().flatten());
+        }
+        shortcuts
+    }
+
     pub fn open_primary(key: &str) {
         if let Some(shortcut) = Self::get_primary_by_key(key) {
             Self::open_shortcut(&shortcut);
@@ -171,7 +255,7 @@ impl ShortcutManager {
         }
     }
 
-    fn open_shortcut(shortcut: &Shortcut) {
+    pub fn open_shortcut(shortcut: &Shortcut) {
         match open::that(shortcut.url.clone()) {
             Ok(_) => println!("Url: {}", shortcut.url),
             Err(error) => println!("{}", error),
@@ -180,16 +264,37 @@ impl ShortcutManager {
 }
 
 impl Meta {
-    pub fn get_token() -> String {
-        match Self::parse() {
-            Ok(meta) => meta.token,
-            Err(_) => String::from(""),
+    /// Returns the token in the `--token-file` if one is given, the one of the login otherwise.
+    ///
+    /// An unreadable `--token-file` is an error, so the commands which need the token
+    /// fail instead of acting as if logged out.
+    pub fn resolve_token() -> Result<Option<String>> {
+        if let Some(ref path) = Config::current().token_file {
+            let token = FILE_TOKEN.with(|token| {
+                token
+                    .get_or_init(|| read_token_file(path).map_err(|error| error.to_string()))
+                    .clone()
+            });
+            return token.map(Some).map_err(|error| anyhow!(error));
         }
+        Ok(Self::parse().ok().map(|meta| meta.token))
     }
+}
 
-    pub fn has_token() -> bool {
-        Self::parse().is_ok()
+/// Returns the token in the file at **path** without surrounding whitespace.
+fn read_token_file(path: &Path) -> Result<String> {
+    let token = fs::read_to_string(path).map_err(|error| {
+        anyhow!(
+            "Could not read the token file {}: {}",
+            path.display(),
+            error
+        )
+    })?;
+    let token = token.trim();
+    if token.is_empty() {
+        bail!("The token file {} is empty.", path.display());
     }
+    Ok(token.to_string())
 }
 
 impl Deref for PrimaryShortcutVec {
@@ -207,3 +312,76 @@ impl Deref for SecondaryShortcutMap {
         &self.0
     }
 }
+
+/// Builds shortcuts for tests, the fields which are not set get defaults.
+#[cfg(test)]
+impl Shortcut {
+    pub(crate) fn test(id: u32, key: &str) -> Shortcut {
+        Shortcut {
+            id,
+            key: key.to_string(),
+            url: format!("https://{}.com", key),
+            title: String::from("Title"),
+            comment: None,
+            domain: String::from("a.com"),
+            open_times: 0,
+            timestamp: 0,
+        }
+    }
+
+    pub(crate) fn with_url(mut self, url: &str) -> Shortcut {
+        self.url = url.to_string();
+        self
+    }
+
+    pub(crate) fn with_title(mut self, title: &str) -> Shortcut {
+        self.title = title.to_string();
+        self
+    }
+
+    pub(crate) fn with_comment(mut self, comment: &str) -> Shortcut {
+        self.comment = Some(comment.to_string());
+        self
+    }
+
+    pub(crate) fn with_domain(mut self, domain: &str) -> Shortcut {
+        self.domain = domain.to_string();
+        self
+    }
+
+    pub(crate) fn with_open_times(mut self, open_times: i32) -> Shortcut {
+        self.open_times = open_times;
+        self
+    }
+
+    pub(crate) fn with_timestamp(mut self, timestamp: i64) -> Shortcut {
+        self.timestamp = timestamp;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_token_file() {
+        let path = std::env::temp_dir().join(format!("anyshortcut-token-{}", std::process::id()));
+        fs::write(&path, "  secret\n").unwrap();
+        assert_eq!(read_token_file(&path).unwrap(), "secret");
+
+        fs::write(&path, " \n").unwrap();
+        let error = read_token_file(&path).unwrap_err().to_string();
+        assert_eq!(
+            error,
+            format!("The token file {} is empty.", path.display())
+        );
+
+        fs::remove_file(&path).unwrap();
+        let error = read_token_file(&path).unwrap_err().to_string();
+        assert!(error.starts_with(&format!(
+            "Could not read the token file {}: ",
+            path.display()
+        )));
+    }
+}
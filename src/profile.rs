@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::store;
+
+/// The profile which is used when no other one is selected.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Named profiles, each with its own API server, token and synced shortcuts.
+///
+/// They are kept in `profiles.json` of the root storage directory. Every profile
+/// except the default one stores its data in `profiles/<name>` below it.
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Profiles {
+    /// The profile used when none is selected with `--profile`.
+    #[serde(default)]
+    pub active: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct Profile {
+    /// The API server of the profile, the official one if not set.
+    pub api_url: Option<String>,
+}
+
+impl Profiles {
+    fn get_file_path() -> Result<PathBuf> {
+        Ok(store::get_root_directory()?.join("profiles.json"))
+    }
+
+    /// Load the profiles, there are none before the first `profile add`.
+    pub fn load() -> Result<Profiles> {
+        let path = Self::get_file_path()?;
+        if !path.exists() {
+            return Ok(Profiles::default());
+        }
+        Ok(serde_json::from_reader(File::open(path)?)?)
+    }
+
+    pub fn persist(&self) -> Result<()> {
+        let file = File::create(Self::get_file_path()?)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    /// Returns the name of the selected profile, from `--profile`, `ANYSHORTCUT_PROFILE`
+    /// or `profile use` in that order.
+    pub fn active_name() -> String {
+        Config::current()
+            .profile
+            .clone()
+            .or_else(|| Profiles::load().ok()?.active)
+            .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+    }
+
+    /// Returns the selected profile, which has to exist unless it is the default one.
+    pub fn active() -> Result<Profile> {
+        let name = Self::active_name();
+        match Profiles::load()?.profiles.remove(&name) {
+            Some(profile) => Ok(profile),
+            None if name == DEFAULT_PROFILE => Ok(Profile::default()),
+            None => bail!(
+                "Unknown profile {}, add it with `profile add {}` first.",
+                name,
+                name
+            ),
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        name == DEFAULT_PROFILE || self.profiles.contains_key(name)
+    }
+}
+
+/// Get the storage directory of the profile **name**, creating it if it doesn't exist.
+pub fn get_profile_directory(name: &str) -> Result<PathBuf> {
+    let mut path = store::get_root_directory()?;
+    if name != DEFAULT_PROFILE {
+        path.push("profiles");
+        path.push(name);
+        fs::create_dir_all(&path)?;
+    }
+    Ok(path)
+}
@@ -1,12 +1,15 @@
 use anyhow::{anyhow, Result};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use std::fs;
-use std::fs::File;
+use std::fs::{self, File};
 use std::path::PathBuf;
 
-/// Get user storage directory.
-pub fn get_store_directory() -> Result<PathBuf> {
+use curl_http::create_private_file;
+
+use crate::profile::{self, Profiles};
+
+/// Get the root storage directory, which holds the profiles.
+pub fn get_root_directory() -> Result<PathBuf> {
     let mut path = dirs::home_dir().ok_or_else(|| anyhow!("Could not find home dir"))?;
     path.push(".anyshortcut");
     // Create the directory if not exist before write date to file.
@@ -14,32 +17,41 @@ pub fn get_store_directory() -> Result<PathBuf> {
     Ok(path)
 }
 
+/// Get user storage directory of the selected profile.
+pub fn get_store_directory() -> Result<PathBuf> {
+    profile::get_profile_directory(&Profiles::active_name())
+}
+
 /// **Storage** trait which required supertraits (Serialize, DeserializeOwned)
 /// to persist() and parse() target file.
 pub trait Storage: Serialize + DeserializeOwned {
     /// Get storage file name.
     fn get_file_name() -> String;
 
-    fn persist(&self) -> Result<()> {
+    /// Get the path of the storage file.
+    fn get_file_path() -> Result<PathBuf> {
         let mut path = get_store_directory()?;
         path.push(Self::get_file_name());
+        Ok(path)
+    }
 
-        let file = File::create(path)?;
+    fn persist(&self) -> Result<()> {
+        let path = Self::get_file_path()?;
+        // The files may hold the token, so only the user may read them.
+        let file = create_private_file(&path)?;
         serde_json::to_writer_pretty(file, &self)?;
         Ok(())
     }
 
     fn parse() -> Result<Self> {
-        let mut path = get_store_directory()?;
-        path.push(Self::get_file_name());
+        let path = Self::get_file_path()?;
 
         let file = File::open(path)?;
         Ok(serde_json::from_reader(file)?)
     }
 
     fn clear() -> Result<()> {
-        let mut path = get_store_directory()?;
-        path.push(Self::get_file_name());
+        let path = Self::get_file_path()?;
 
         if path.exists() {
             fs::remove_file(path).unwrap();
@@ -0,0 +1,56 @@
+use std::env;
+use std::io::{self, IsTerminal};
+use std::str::FromStr;
+
+use ansi_term::Style;
+
+use crate::config::Config;
+use crate::utils::output;
+
+/// When to colorize the output, see the `--color` option.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum ColorChoice {
+    /// Only when stdout is a terminal and `NO_COLOR` is not set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<ColorChoice, String> {
+        match value {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("Invalid color choice: {}", value)),
+        }
+    }
+}
+
+/// Whether the output should be colorized according to the `--color` option.
+pub fn enabled() -> bool {
+    match Config::current().color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            !output::is_redirected()
+                && io::stdout().is_terminal()
+                && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+        }
+    }
+}
+
+/// Returns the **style** if colors are enabled, otherwise a plain style.
+///
+/// All colored output has to go through here, machine readable output
+/// must not be styled at all.
+pub fn style(style: impl Into<Style>) -> Style {
+    if enabled() {
+        style.into()
+    } else {
+        Style::new()
+    }
+}
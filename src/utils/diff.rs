@@ -0,0 +1,314 @@
+use std::collections::HashSet;
+
+use ansi_term::Color::{Green, Red, Yellow};
+use anyhow::Result;
+use serde_derive::Serialize;
+use serde_json::Value;
+
+use crate::models::Shortcut;
+use crate::utils::color::style;
+use crate::utils::field;
+use crate::utils::output::outln;
+
+/// How `sync --diff` prints the changes.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum DiffFormat {
+    Text,
+    Json,
+}
+
+/// A change of a shortcut between the local and the fetched shortcuts.
+#[derive(Serialize, Debug)]
+#[serde(tag = "change", rename_all = "lowercase")]
+pub enum Change {
+    Added {
+        shortcut: Shortcut,
+    },
+    Removed {
+        shortcut: Shortcut,
+    },
+    Changed {
+        id: u32,
+        key: String,
+        domain: String,
+        fields: Vec<FieldChange>,
+    },
+}
+
+/// A field of a shortcut which has another value after the sync.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+/// Changes are equal with equal contents, shortcuts alone are equal by their id.
+impl PartialEq for Change {
+    fn eq(&self, other: &Change) -> bool {
+        match (self, other) {
+            (Change::Added { shortcut: a }, Change::Added { shortcut: b })
+            | (Change::Removed { shortcut: a }, Change::Removed { shortcut: b }) => {
+                a.same_content(b)
+            }
+            (
+                Change::Changed {
+                    id,
+                    key,
+                    domain,
+                    fields,
+                },
+                Change::Changed {
+                    id: other_id,
+                    key: other_key,
+                    domain: other_domain,
+                    fields: other_fields,
+                },
+            ) => {
+                id == other_id
+                    && key == other_key
+                    && domain == other_domain
+                    && fields == other_fields
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Change {
+    fn sort_key(&self) -> (&str, &str) {
+        match self {
+            Change::Added { shortcut } | Change::Removed { shortcut } => {
+                (&shortcut.key, &shortcut.domain)
+            }
+            Change::Changed { key, domain, .. } => (key, domain),
+        }
+    }
+}
+
+/// Compare the **old** shortcuts with the **new** ones by their id, sorted by key.
+pub fn diff<'a>(
+    old: impl IntoIterator<Item = &'a Shortcut>,
+    new: impl IntoIterator<Item = &'a Shortcut>,
+) -> Vec<Change> {
+    let mut old: HashSet<&Shortcut> = old.into_iter().collect();
+    let mut changes = vec![];
+    for shortcut in new {
+        match old.take(shortcut) {
+            Some(old) => {
+                let fields = diff_fields(old, shortcut);
+                if !fields.is_empty() {
+                    changes.push(Change::Changed {
+                        id: shortcut.id,
+                        key: shortcut.key.clone(),
+                        domain: shortcut.domain.clone(),
+                        fields,
+                    });
+                }
+            }
+            None => changes.push(Change::Added {
+                shortcut: shortcut.clone(),
+            }),
+        }
+    }
+    changes.extend(old.into_iter().map(|shortcut| Change::Removed {
+        shortcut: shortcut.clone(),
+    }));
+    changes.sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+    changes
+}
+
+/// Compare the serialized fields, so a new field of `Shortcut` is covered without changes here.
+pub(crate) fn diff_fields(old: &Shortcut, new: &Shortcut) -> Vec<FieldChange> {
+    let (old, new) = match (serde_json::to_value(old), serde_json::to_value(new)) {
+        (Ok(Value::Object(old)), Ok(Value::Object(new))) => (old, new),
+        _ => return vec![],
+    };
+    new.into_iter()
+        .filter_map(|(field, new)| {
+            let old = old.get(&field).cloned().unwrap_or(Value::Null);
+            if old == new {
+                None
+            } else {
+                Some(FieldChange { field, old, new })
+            }
+        })
+        .collect()
+}
+
+/// Print the **changes** in the **format**.
+pub fn print(changes: &[Change], format: DiffFormat) -> Result<()> {
+    if format == DiffFormat::Json {
+        outln!("{}", serde_json::to_string_pretty(changes)?);
+        return Ok(());
+    }
+    if changes.is_empty() {
+        outln!("No changes.");
+    }
+    for change in changes {
+        match change {
+            Change::Added { shortcut } => outln!(
+                "{} {}  {}  {}",
+                style(Green).paint("+"),
+                name(&shortcut.key, &shortcut.domain),
+                shortcut.url,
+                shortcut.title
+            ),
+            Change::Removed { shortcut } => outln!(
+                "{} {}  {}  {}",
+                style(Red).paint("-"),
+                name(&shortcut.key, &shortcut.domain),
+                shortcut.url,
+                shortcut.title
+            ),
+            Change::Changed {
+                key,
+                domain,
+                fields,
+                ..
+            } => {
+                outln!("{} {}", style(Yellow).paint("~"), name(key, domain));
+                for change in fields {
+                    outln!(
+                        "    {}: {} → {}",
+                        change.field,
+                        plain(&change.old),
+                        plain(&change.new)
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn name(key: &str, domain: &str) -> String {
+    format!("{} ({})", key.to_uppercase(), domain)
+}
+
+fn plain(value: &Value) -> String {
+    match value {
+        Value::Null => String::from("none"),
+        value => field::to_plain(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff() {
+        let old = vec![
+            Shortcut::test(1, "a"),
+            Shortcut::test(2, "b"),
+            Shortcut::test(3, "c"),
+        ];
+        let new = vec![
+            Shortcut::test(2, "x")
+                .with_url("https://b.com/new")
+                .with_comment("moved"),
+            Shortcut::test(3, "c"),
+            Shortcut::test(4, "d"),
+        ];
+
+        let changes = diff(&old, &new);
+        assert_eq!(changes.len(), 3);
+        assert_eq!(
+            changes[0],
+            Change::Removed {
+                shortcut: old[0].clone()
+            }
+        );
+        assert_eq!(
+            changes[1],
+            Change::Added {
+                shortcut: new[2].clone()
+            }
+        );
+        assert_eq!(
+            changes[2],
+            Change::Changed {
+                id: 2,
+                key: String::from("x"),
+                domain: String::from("a.com"),
+                fields: vec![
+                    FieldChange {
+                        field: String::from("comment"),
+                        old: Value::Null,
+                        new: json!("moved"),
+                    },
+                    FieldChange {
+                        field: String::from("key"),
+                        old: json!("b"),
+                        new: json!("x"),
+                    },
+                    FieldChange {
+                        field: String::from("url"),
+                        old: json!("https://b.com"),
+                        new: json!("https://b.com/new"),
+                    },
+                ],
+            }
+        );
+        assert!(diff(&new, &new).is_empty());
+        // A change only equals one with the same contents.
+        assert_ne!(
+            changes[0],
+            Change::Removed {
+                shortcut: Shortcut::test(1, "a").with_url("https://other.com")
+            }
+        );
+
+        let json = serde_json::to_value(&changes[2]).unwrap();
+        assert_eq!(json["change"], json!("changed"));
+        assert_eq!(json["fields"][1]["old"], json!("b"));
+    }
+
+    #[test]
+    fn test_shortcut_identity() {
+        let old = [
+            Shortcut::test(1, "a"),
+            Shortcut::test(2, "b"),
+            Shortcut::test(3, "c"),
+        ];
+        let new = [
+            Shortcut::test(2, "b").with_url("https://b.com/new"),
+            Shortcut::test(3, "c"),
+            Shortcut::test(4, "d"),
+        ];
+        // Shortcuts are the same by their id, even with other contents.
+        assert_eq!(old[1], new[0]);
+        assert!(!old[1].same_content(&new[0]));
+        assert!(old[2].same_content(&new[1]));
+
+        let ids = |shortcuts: Vec<&Shortcut>| {
+            let mut ids: Vec<u32> = shortcuts.iter().map(|shortcut| shortcut.id).collect();
+            ids.sort_unstable();
+            ids
+        };
+        let old_set: HashSet<&Shortcut> = old.iter().collect();
+        let new_set: HashSet<&Shortcut> = new.iter().collect();
+        assert_eq!(ids(new_set.difference(&old_set).copied().collect()), [4]);
+        assert_eq!(ids(old_set.difference(&new_set).copied().collect()), [1]);
+        let changed = new_set
+            .iter()
+            .filter(|shortcut| {
+                old_set
+                    .get(*shortcut)
+                    .is_some_and(|old| !old.same_content(shortcut))
+            })
+            .copied()
+            .collect();
+        assert_eq!(ids(changed), [2]);
+
+        // A shortcut with a known id replaces the old one instead of being added.
+        let mut merged: HashSet<&Shortcut> = old.iter().collect();
+        for shortcut in &new {
+            merged.replace(shortcut);
+        }
+        assert_eq!(merged.len(), 4);
+        assert_eq!(merged.get(&old[1]).unwrap().url, "https://b.com/new");
+    }
+}
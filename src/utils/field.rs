@@ -0,0 +1,56 @@
+use serde_json::Value;
+
+/// Returns the value at the dotted **path** of **value**, like `url` or `meta.total`.
+///
+/// Array items are addressed by their index, e.g. `tags.0`.
+pub fn select<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(value, |value, segment| match value {
+            Value::Object(map) => map.get(segment),
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+            _ => None,
+        })
+}
+
+/// Format a selected value for a line of output: strings without quotes,
+/// `null` as an empty line and everything else as JSON.
+pub fn to_plain(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
+
+/// Format a value as a cell of tab-separated output like [`to_plain`], with tabs
+/// and line breaks turned into spaces, so every record stays one line.
+pub fn to_tsv_cell(value: &Value) -> String {
+    to_plain(value).replace(['\t', '\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_select() {
+        let value = json!({"url": "https://a.com", "meta": {"total": 3, "tags": ["x", "y"]}});
+        assert_eq!(select(&value, "url"), Some(&json!("https://a.com")));
+        assert_eq!(select(&value, "meta.total"), Some(&json!(3)));
+        assert_eq!(select(&value, "meta.tags.1"), Some(&json!("y")));
+        assert_eq!(select(&value, "meta.missing"), None);
+        assert_eq!(select(&value, "url.host"), None);
+        assert_eq!(select(&value, "meta.tags.x"), None);
+    }
+
+    #[test]
+    fn test_to_plain() {
+        assert_eq!(to_plain(&json!("https://a.com")), "https://a.com");
+        assert_eq!(to_plain(&json!(null)), "");
+        assert_eq!(to_plain(&json!(3)), "3");
+        assert_eq!(to_plain(&json!(["x"])), r#"["x"]"#);
+        assert_eq!(to_tsv_cell(&json!("a\tb\r\nc")), "a b  c");
+        assert_eq!(to_tsv_cell(&json!(null)), "");
+    }
+}
@@ -0,0 +1,96 @@
+/// A fuzzy match of a query in a text.
+#[derive(Debug, PartialEq)]
+pub struct FuzzyMatch {
+    /// Higher is better.
+    pub score: i64,
+    /// Indices of the matched chars in the text.
+    pub positions: Vec<usize>,
+}
+
+/// Match the chars of **query** in order against **text**, ignoring case and
+/// whitespace in the query.
+///
+/// Consecutive chars and chars at the start of a word score higher, so an exact
+/// substring is always preferred over the same chars spread across the text.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<FuzzyMatch> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(lowercase)
+        .collect();
+    if query.is_empty() {
+        return None;
+    }
+    let text: Vec<char> = text.chars().collect();
+    let lower: Vec<char> = text.iter().copied().map(lowercase).collect();
+
+    let substring = lower
+        .windows(query.len())
+        .position(|window| window == &query[..]);
+    let (positions, mut score): (Vec<usize>, i64) = match substring {
+        Some(start) => (
+            (start..start + query.len()).collect(),
+            4 * query.len() as i64,
+        ),
+        None => (subsequence(&query, &lower)?, 0),
+    };
+
+    for (index, &position) in positions.iter().enumerate() {
+        score += 16;
+        if index > 0 && positions[index - 1] + 1 == position {
+            score += 8;
+        } else if index > 0 {
+            score -= (position - positions[index - 1]) as i64;
+        }
+        if position == 0 || !text[position - 1].is_alphanumeric() {
+            score += 8;
+        }
+    }
+    score -= positions[0] as i64;
+    Some(FuzzyMatch { score, positions })
+}
+
+fn subsequence(query: &[char], text: &[char]) -> Option<Vec<usize>> {
+    let mut positions = Vec::with_capacity(query.len());
+    let mut chars = text.iter().enumerate();
+    for c in query {
+        let (position, _) = chars.find(|(_, other)| *other == c)?;
+        positions.push(position);
+    }
+    Some(positions)
+}
+
+fn lowercase(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert_eq!(
+            fuzzy_match("goo", "Google").unwrap().positions,
+            vec![0, 1, 2]
+        );
+        assert_eq!(
+            fuzzy_match("gt", "Google Translate").unwrap().positions,
+            vec![0, 7]
+        );
+        assert_eq!(
+            fuzzy_match("G T", "google translate").unwrap().positions,
+            vec![0, 7]
+        );
+        assert!(fuzzy_match("xyz", "Google").is_none());
+        assert!(fuzzy_match(" ", "Google").is_none());
+    }
+
+    #[test]
+    fn test_fuzzy_match_ranking() {
+        let score = |text| fuzzy_match("drop", text).unwrap().score;
+        assert!(score("Dropbox") > score("My Dropbox"));
+        assert!(score("My Dropbox") > score("Dr. Open"));
+        assert!(score("Dr. Open") > score("address of props"));
+    }
+}
@@ -1 +1,7 @@
+pub mod color;
+pub mod diff;
+pub mod field;
+pub mod fuzzy;
+pub mod output;
+pub mod sort;
 pub mod ui;
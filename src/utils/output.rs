@@ -0,0 +1,148 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use anyhow::{bail, Context, Result};
+
+use crate::config::Config;
+
+thread_local! {
+    static OUTPUT: RefCell<Option<OutputFile>> = const { RefCell::new(None) };
+}
+
+/// The temporary file next to the `--output-file` which gets the output until
+/// the command succeeded, so the file is either replaced completely or not at all.
+struct OutputFile {
+    temp: PathBuf,
+    writer: BufWriter<File>,
+    /// The first failed write, reported when finishing.
+    error: Option<io::Error>,
+}
+
+/// Print a line of the primary output of a command, which goes into the
+/// `--output-file` if one is given and to stdout otherwise.
+///
+/// Messages about what is going on keep using `println!`.
+macro_rules! outln {
+    () => {
+        $crate::utils::output::print(format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::utils::output::print(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+pub(crate) use outln;
+
+/// Start collecting the primary output for the `--output-file`, if there is one.
+///
+/// Fails if the file exists and `--force` was not given.
+pub fn begin() -> Result<()> {
+    let config = Config::current();
+    if let Some(ref path) = config.output_file {
+        if path.exists() && !config.force {
+            bail!(
+                "{} already exists, pass --force to overwrite it.",
+                path.display()
+            );
+        }
+        let output =
+            create_temp(path).with_context(|| format!("Could not write {}", path.display()))?;
+        OUTPUT.with(|cell| *cell.borrow_mut() = Some(output));
+    }
+    Ok(())
+}
+
+/// Whether the primary output goes into a file.
+pub fn is_redirected() -> bool {
+    Config::current().output_file.is_some()
+}
+
+pub fn print(args: fmt::Arguments) {
+    OUTPUT.with(|cell| match *cell.borrow_mut() {
+        Some(ref mut output) => {
+            if output.error.is_none() {
+                output.error = output.writer.write_fmt(args).err();
+            }
+        }
+        None => print!("{}", args),
+    });
+}
+
+/// Move the output into the `--output-file` once the command succeeded.
+pub fn finish() -> Result<()> {
+    let output = match OUTPUT.with(|cell| cell.borrow_mut().take()) {
+        Some(output) => output,
+        None => return Ok(()),
+    };
+    let config = Config::current();
+    let path = config.output_file.as_ref().unwrap();
+    let temp = output.temp.clone();
+    complete(output, path)
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&temp);
+        })
+        .with_context(|| format!("Could not write {}", path.display()))
+}
+
+/// Remove the output of a failed command, so no partial `--output-file` is left.
+pub fn discard() {
+    if let Some(output) = OUTPUT.with(|cell| cell.borrow_mut().take()) {
+        drop(output.writer);
+        let _ = fs::remove_file(output.temp);
+    }
+}
+
+fn create_temp(path: &Path) -> io::Result<OutputFile> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)?;
+    }
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(format!(".{}.tmp", process::id()));
+    let temp = PathBuf::from(temp);
+    Ok(OutputFile {
+        writer: BufWriter::new(File::create(&temp)?),
+        temp,
+        error: None,
+    })
+}
+
+fn complete(mut output: OutputFile, path: &Path) -> io::Result<()> {
+    if let Some(error) = output.error.take() {
+        return Err(error);
+    }
+    output.writer.flush()?;
+    drop(output.writer);
+    fs::rename(&output.temp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_file_is_written_at_once() {
+        let path = std::env::temp_dir().join(format!("anyshortcut-output-{}", process::id()));
+        let output = create_temp(&path).unwrap();
+        let temp = output.temp.clone();
+        OUTPUT.with(|cell| *cell.borrow_mut() = Some(output));
+        print(format_args!("partial"));
+        discard();
+        assert!(!temp.exists());
+        assert!(!path.exists());
+
+        let output = create_temp(&path).unwrap();
+        OUTPUT.with(|cell| *cell.borrow_mut() = Some(output));
+        print(format_args!("complete"));
+        let output = OUTPUT.with(|cell| cell.borrow_mut().take()).unwrap();
+        complete(output, &path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "complete");
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use crate::models::Shortcut;
+
+/// What to sort shortcuts by, see the `--sort` option of `list`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SortField {
+    Key,
+    Title,
+    /// The newest first.
+    Created,
+    /// The most opened first.
+    Opens,
+}
+
+impl FromStr for SortField {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<SortField, String> {
+        match value {
+            "key" => Ok(SortField::Key),
+            "title" => Ok(SortField::Title),
+            "created" => Ok(SortField::Created),
+            "opens" => Ok(SortField::Opens),
+            _ => Err(format!("Invalid sort field: {}", value)),
+        }
+    }
+}
+
+/// Sort the **shortcuts** stably by the **field**, the other way round when **reverse**.
+///
+/// Shortcuts without a creation time stay last when sorting by `created`.
+pub fn sort(shortcuts: &mut [Shortcut], field: SortField, reverse: bool) {
+    shortcuts.sort_by(|a, b| {
+        if field == SortField::Created {
+            match (a.timestamp > 0, b.timestamp > 0) {
+                (true, false) => return Ordering::Less,
+                (false, true) => return Ordering::Greater,
+                (false, false) => return Ordering::Equal,
+                (true, true) => {}
+            }
+        }
+        let ordering = match field {
+            SortField::Key => a.key.to_lowercase().cmp(&b.key.to_lowercase()),
+            SortField::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+            SortField::Created => b.timestamp.cmp(&a.timestamp),
+            SortField::Opens => b.open_times.cmp(&a.open_times),
+        };
+        if reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(shortcuts: &[Shortcut], field: SortField, reverse: bool) -> String {
+        let mut shortcuts = shortcuts.to_vec();
+        sort(&mut shortcuts, field, reverse);
+        shortcuts
+            .iter()
+            .map(|shortcut| shortcut.key.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_sort() {
+        let shortcuts = vec![
+            Shortcut::test(1, "b")
+                .with_title("beta")
+                .with_timestamp(0)
+                .with_open_times(5),
+            Shortcut::test(1, "A")
+                .with_title("Gamma")
+                .with_timestamp(20)
+                .with_open_times(1),
+            Shortcut::test(1, "c")
+                .with_title("alpha")
+                .with_timestamp(10)
+                .with_open_times(5),
+            Shortcut::test(1, "d")
+                .with_title("delta")
+                .with_timestamp(0)
+                .with_open_times(0),
+        ];
+        assert_eq!(keys(&shortcuts, SortField::Key, false), "Abcd");
+        assert_eq!(keys(&shortcuts, SortField::Key, true), "dcbA");
+        assert_eq!(keys(&shortcuts, SortField::Title, false), "cbdA");
+        // Shortcuts without a creation time stay last and in their order.
+        assert_eq!(keys(&shortcuts, SortField::Created, false), "Acbd");
+        assert_eq!(keys(&shortcuts, SortField::Created, true), "cAbd");
+        // The sort is stable for equal open times.
+        assert_eq!(keys(&shortcuts, SortField::Opens, false), "bcAd");
+        assert_eq!(keys(&shortcuts, SortField::Opens, true), "dAbc");
+
+        assert_eq!("opens".parse(), Ok(SortField::Opens));
+        assert!("last_used".parse::<SortField>().is_err());
+    }
+}
@@ -32,3 +32,13 @@ pub fn prompt(message: &str) -> io::Result<String> {
         }
     }
 }
+
+/// Ask for a confirmation which has to be typed out as `yes`.
+pub fn prompt_for_yes(message: &str) -> io::Result<bool> {
+    print!("{} Type yes to continue: ", message);
+    io::stdout().flush()?;
+
+    let mut buffer = String::new();
+    io::stdin().read_line(&mut buffer)?;
+    Ok(buffer.trim() == "yes")
+}
@@ -8,11 +8,20 @@ use proc_macro::TokenStream;
 /// A small derive procedure macro to implement **Storage** trait.
 ///
 /// ```
-///  #[derive(Storage)]
-///  #[store_at = "meta.json"]
-///  pub struct Meta {
-///      pub token: String,
-///  }
+/// use storage_derive::Storage;
+///
+/// // The derive implements the `Storage` trait which is in scope.
+/// trait Storage {
+///     fn get_file_name() -> String;
+/// }
+///
+/// #[derive(Storage)]
+/// #[store_at = "meta.json"]
+/// pub struct Meta {
+///     pub token: String,
+/// }
+///
+/// assert_eq!(Meta::get_file_name(), "meta.json");
 /// ```
 /// The `store_at` attribute is required.
 ///